@@ -1,76 +1,255 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128};
-use cw2::set_contract_version;
+use cosmwasm_std::storage_keys::to_length_prefixed;
+use cosmwasm_std::{
+    from_json, to_json_binary, to_json_string, Addr, Binary, Coin, Decimal, Deps, DepsMut, Empty,
+    Env, Event, HexBinary, Int256, MessageInfo, Order, Response, StdResult, Storage, Uint128,
+};
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::{range_with_prefix, Bound, KeyDeserialize, Map, PrimaryKey};
+use cw_utils::{Duration, Expiration};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, GetCountResponse, GetStakeResponse, InstantiateMsg, QueryMsg};
-use crate::state::{State, STATE, STAKES};
+use crate::helpers::unstake_commitment;
+use crate::migrations;
+use crate::msg::{
+    AfterStakeMsg, AggregateResponse, AssetInfoResponse, ConfigUpdate, DailyStatsEntry,
+    DailyStatsResponse, DistributionBucket, DistributionResponse, ExecuteMsg, FeeDiscountResponse,
+    GetCountResponse, GetStakeResponse, GetStakesResponse, GuardianResponse, HasMinStakeResponse,
+    HashedStakeEntry, HashedStakesResponse, HealthResponse, InstantiateMsg, LedgerBalance,
+    MalformedRecord, MetricsResponse, MigrateMsg, NewGuardian, NonceResponse, PendingExit,
+    PendingImport, PendingImportsResponse, PortfolioLock, PortfolioResponse, Position, ProfileEntry,
+    ProfilesResponse, QueryMsg, RebatesResponse, ReceiptCallback, ShareOfPoolResponse,
+    StakeCallback, StakeEntry, StakeReceipt, TrialBalanceResponse, TrustedPoolEntry,
+    TrustedPoolsResponse, UnstakeOrderEntry, UnstakeOrdersResponse, UserStatsResponse, Violation,
+    ViolationKind, ViolationsResponse,
+};
+use crate::state::{
+    Clawback, CommitReveal, Config, Evacuation, ExitFeeTier, FeeDiscountTier, Guardian, Heartbeat,
+    Ledger, LedgerAccount, LifetimeTotals, LoweredDelay, OrderCondition, PendingUnstake,
+    PoolMetadata, Profile, QueueDepth, RebateRates, RebuildKind, RebuildProgress, RecentDeposit, StakeTranche, State,
+    TvlAlert, TvlWindow, UnstakeCommit, UnstakeConfirmation, UnstakeOrder, UserStats, CLAWBACKS,
+    CONFIG, DAILY_ACTIVE, DAILY_STATS, DISTRIBUTION, EVACUATION, GUARDIAN, LEDGER, LIFETIME_TOTALS,
+    NEXT_ORDER_ID, NONCES, OWNER_HEARTBEAT, PAUSED, PENDING_UNSTAKES, PROFILES, QUEUE_DEPTH, REBATE_ACCOUNTS,
+    REBATE_POOL, REBUILD, RECENT_DEPOSITS, SIGNING_KEYS, STAKER_HASHES,
+    STAKER_HASH_SALT, STAKES, STAKE_TRANCHES, STATE, TOTAL_STAKED, TRUSTED_POOLS, TVL_WINDOW, UNSTAKE_COMMITS,
+    UNSTAKE_CONFIRMATIONS, UNSTAKE_ORDERS, USER_STATS,
+};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:staking_contract";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const STAKE_DENOM: &str = "token";
+const SECONDS_PER_DAY: u64 = 86_400;
+
+// pagination bounds for range queries
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+const MAX_AGGREGATE_QUERIES: usize = 10;
+
+// stakers scanned per `Rebuild` call
+const DEFAULT_REBUILD_LIMIT: u32 = 100;
+const MAX_REBUILD_LIMIT: u32 = 500;
+
+#[cfg(feature = "testnet")]
+const FAUCET_AMOUNT: u128 = 1_000;
+#[cfg(feature = "testnet")]
+const FAUCET_COOLDOWN: Duration = Duration::Time(86_400);
+
+const MAX_DISPLAY_NAME_LEN: usize = 64;
+const MAX_URL_LEN: usize = 256;
+const MAX_AVATAR_HASH_LEN: usize = 128;
+
+const MAX_POOL_NAME_LEN: usize = 64;
+const MAX_POOL_DESCRIPTION_LEN: usize = 512;
+/// Most addresses a single payout can be split between
+const MAX_PAYOUT_RECIPIENTS: usize = 10;
+/// Longest unstake confirmation delay, about a month of 6 second blocks
+const MAX_CONFIRMATION_BLOCKS: u64 = 432_000;
+/// Longest deposit lock, about a month of 6 second blocks
+const MAX_DEPOSIT_LOCK_BLOCKS: u64 = 432_000;
+/// Longest wait between an unstake commit and its reveal
+const MAX_REVEAL_DELAY_BLOCKS: u64 = 432_000;
+/// Longest owner inactivity a guardian can be set to wait for, about ten years either way
+const MAX_GUARDIAN_INACTIVITY_BLOCKS: u64 = 52_560_000;
+const MAX_GUARDIAN_INACTIVITY_SECONDS: u64 = 10 * 365 * SECONDS_PER_DAY;
+/// Most tranches kept per staker; past this, new stake joins the newest tranche
+const MAX_STAKE_TRANCHES: usize = 32;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
-    let state = State {
-        count: msg.count,
-        owner: info.sender.clone(),
-    };
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    STATE.save(deps.storage, &state)?;
+    init_state(deps.storage, info.sender.clone(), msg.count)?;
+    if let Some(asset_metadata) = msg.asset_metadata {
+        CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+            config.asset_metadata = Some(asset_metadata);
+            Ok(config)
+        })?;
+    }
 
-    Ok(Response::new()
+    let mut response = Response::new()
         .add_attribute("method", "instantiate")
-        .add_attribute("owner", info.sender)
-        .add_attribute("count", msg.count.to_string()))
+        .add_attribute("owner", info.sender.clone())
+        .add_attribute("count", msg.count.to_string());
+
+    // genesis allocations are funded by the instantiation message itself
+    if let Some(initial_stakes) = msg.initial_stakes {
+        let (credits, seeded) = validate_stake_batch(deps.as_ref(), &info, initial_stakes)?;
+        for (staker, amount) in &credits {
+            credit_stake(deps.storage, &env, staker, *amount)?;
+        }
+        post(deps.storage, &[(LedgerAccount::Cash, LedgerAccount::Principal, seeded)])?;
+        response = response
+            .add_attribute("initial_stakers", credits.len().to_string())
+            .add_attribute("initial_staked", seeded.to_string());
+    }
+
+    Ok(response)
+}
+
+/// Writes the initial staking state without touching cw2 contract info, so a host contract
+/// embedding this logic through the `library` feature keeps its own name and version.
+pub fn init_state(storage: &mut dyn Storage, owner: Addr, count: i32) -> StdResult<()> {
+    STATE.save(storage, &State { count, owner })?;
+    TOTAL_STAKED.save(storage, &Uint128::zero())?;
+    PAUSED.save(storage, &false)?;
+    CONFIG.save(storage, &Config::default())?;
+    LIFETIME_TOTALS.save(storage, &LifetimeTotals::default())?;
+    LEDGER.save(storage, &Ledger::default())?;
+    Ok(())
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    if !info.funds.is_empty() && !msg.accepts_funds() {
+        return Err(ContractError::UnexpectedFunds {});
+    }
     match msg {
-        ExecuteMsg::Increment {} => execute::increment(deps),
-        ExecuteMsg::Reset { count } => execute::reset(deps, info, count),
-        ExecuteMsg::Stake { amount } => execute::stake(deps, info, amount),
-        ExecuteMsg::Unstake { amount } => execute::unstake(deps, info, amount),
+        ExecuteMsg::Increment {} => execute::increment(deps, info),
+        ExecuteMsg::Reset { count } => execute::reset(deps, env, info, count),
+        ExecuteMsg::Stake { amount } => execute::stake(deps, env, info, amount),
+        ExecuteMsg::Unstake { amount, min_receive, recipients } => {
+            execute::unstake(deps, env, info, amount, min_receive, recipients)
+        }
+        ExecuteMsg::SetUnstakeConfirmation { blocks } => {
+            execute::set_unstake_confirmation(deps, env, info, blocks)
+        }
+        ExecuteMsg::ConfirmUnstake {} => execute::confirm_unstake(deps, env, info),
+        ExecuteMsg::AbortUnstake {} => execute::abort_unstake(deps, info),
+        ExecuteMsg::MigrateStake { target_pool, amount } => {
+            execute::migrate_stake(deps, env, info, target_pool, amount)
+        }
+        ExecuteMsg::CommitUnstake { hash } => execute::commit_unstake(deps, env, info, hash),
+        ExecuteMsg::RevealUnstake {
+            amount,
+            salt,
+            min_receive,
+        } => execute::reveal_unstake(deps, env, info, amount, salt, min_receive),
+        ExecuteMsg::StakeFor { recipient, callback, receipt } => {
+            execute::stake_for(deps, env, info, recipient, callback, receipt)
+        }
+        ExecuteMsg::BatchStakeFor { recipients } => execute::batch_stake_for(deps, env, info, recipients),
+        ExecuteMsg::ImportStakes {
+            stakes,
+            confirm_within,
+        } => execute::import_stakes(deps, env, info, stakes, confirm_within),
+        ExecuteMsg::ConfirmImport {} => execute::confirm_import(deps, env, info),
+        ExecuteMsg::ClawbackImport { address } => execute::clawback_import(deps, env, info, address),
+        ExecuteMsg::CheckInvariants {} => execute::check_invariants(deps, env, info),
+        ExecuteMsg::Rebuild { kind, limit } => execute::rebuild(deps, env, info, kind, limit),
+        ExecuteMsg::SetPaused { paused } => execute::set_paused(deps, env, info, paused),
+        ExecuteMsg::SetProfile {
+            display_name,
+            url,
+            avatar_hash,
+        } => execute::set_profile(deps, env, info, display_name, url, avatar_hash),
+        ExecuteMsg::SetSigningKey { pubkey } => execute::set_signing_key(deps, info, pubkey),
+        ExecuteMsg::InvalidateNonce {} => execute::invalidate_nonce(deps, info),
+        ExecuteMsg::UpdateConfig(update) => execute::update_config(deps, env, info, *update),
+        ExecuteMsg::SetFeeDiscountTiers { tiers } => execute::set_fee_discount_tiers(deps, env, info, tiers),
+        ExecuteMsg::SetExitFeeTiers { tiers } => execute::set_exit_fee_tiers(deps, env, info, tiers),
+        ExecuteMsg::SetPoolMetadata { metadata } => execute::set_pool_metadata(deps, env, info, metadata),
+        ExecuteMsg::SetRejectContractStakers { reject } => {
+            execute::set_reject_contract_stakers(deps, env, info, reject)
+        }
+        ExecuteMsg::SetTvlAlert { alert } => execute::set_tvl_alert(deps, env, info, alert),
+        ExecuteMsg::SetEscrow { address } => execute::set_escrow(deps, env, info, address),
+        ExecuteMsg::SetGuardian { guardian } => execute::set_guardian(deps, env, info, guardian),
+        ExecuteMsg::ClaimOwnership {} => execute::claim_ownership(deps, env, info),
+        ExecuteMsg::AddTrustedPool { address } => execute::add_trusted_pool(deps, env, info, address),
+        ExecuteMsg::RemoveTrustedPool { address } => execute::remove_trusted_pool(deps, env, info, address),
+        ExecuteMsg::SetCommitReveal { rule } => execute::set_commit_reveal(deps, env, info, rule),
+        ExecuteMsg::SetDepositLockBlocks { blocks } => execute::set_deposit_lock_blocks(deps, env, info, blocks),
+        ExecuteMsg::SetRecoveryMode { enabled } => execute::set_recovery_mode(deps, env, info, enabled),
+        ExecuteMsg::SetDepositsCloseAt { close_at } => execute::set_deposits_close_at(deps, env, info, close_at),
+        ExecuteMsg::ScheduleUnstake {
+            amount,
+            execute_at,
+            bounty,
+            condition,
+        } => execute::schedule_unstake(deps, env, info, amount, execute_at, bounty, condition),
+        ExecuteMsg::ExecuteUnstakeOrder { id } => execute::execute_unstake_order(deps, env, info, id),
+        ExecuteMsg::CancelUnstakeOrder { id } => execute::cancel_unstake_order(deps, env, info, id),
+        ExecuteMsg::SetRebateRates { rates } => execute::set_rebate_rates(deps, env, info, rates),
+        ExecuteMsg::FundRebates {} => execute::fund_rebates(deps, env, info),
+        ExecuteMsg::ClaimRebates { recipients } => execute::claim_rebates(deps, env, info, recipients),
+        #[cfg(feature = "testnet")]
+        ExecuteMsg::Faucet {} => execute::faucet(deps, env, info),
+        ExecuteMsg::EmergencyEvacuate {} => execute::emergency_evacuate(deps, env, info),
     }
 }
 
 pub mod execute {
     use super::*;
 
-    pub fn increment(deps: DepsMut) -> Result<Response, ContractError> {
-        STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
+    pub fn increment(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+        let state = STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
             state.count += 1;
             Ok(state)
         })?;
 
-        Ok(Response::new().add_attribute("action", "increment"))
+        Ok(Response::new()
+            .add_attribute("action", "increment")
+            .add_attribute("sender", info.sender)
+            .add_attribute("count", state.count.to_string()))
     }
 
-    pub fn reset(deps: DepsMut, info: MessageInfo, count: i32) -> Result<Response, ContractError> {
+    pub fn reset(deps: DepsMut, env: Env, info: MessageInfo, count: i32) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
         STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
-            if info.sender != state.owner {
-                return Err(ContractError::Unauthorized {});
-            }
             state.count = count;
             Ok(state)
         })?;
-        Ok(Response::new().add_attribute("action", "reset"))
+        Ok(Response::new()
+            .add_attribute("action", "reset")
+            .add_attribute("sender", info.sender)
+            .add_attribute("count", count.to_string()))
     }
 
-    pub fn stake(deps: DepsMut, info: MessageInfo, amount: Uint128) -> Result<Response, ContractError> {
-        if info.funds.is_empty() || info.funds[0].amount < amount {
+    pub fn stake(deps: DepsMut, env: Env, info: MessageInfo, amount: Uint128) -> Result<Response, ContractError> {
+        ensure_not_paused(deps.as_ref())?;
+
+        let funds = match info.funds.as_slice() {
+            [coin] if coin.denom == STAKE_DENOM => coin.amount,
+            [] => Uint128::zero(),
+            _ => return Err(ContractError::InvalidDenom {}),
+        };
+        if funds < amount {
             return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
                 "Insufficient funds sent for staking",
             )));
@@ -81,245 +260,5759 @@ pub mod execute {
                 "Stake amount must be greater than zero",
             )));
         }
-    
-        STAKES.update(deps.storage, &info.sender, |balance| -> StdResult<_> {
-            Ok(balance.unwrap_or(Uint128::zero()) + amount)
-        })?;
-    
+
+        let config = CONFIG.load(deps.storage)?;
+        ensure_deposits_open(&env, &config)?;
+        ensure_not_contract(deps.as_ref(), &config, &info.sender)?;
+        let new_balance = credit_stake(deps.storage, &env, &info.sender, amount)?;
+        post(deps.storage, &[(LedgerAccount::Cash, LedgerAccount::Principal, amount)])?;
+        record_deposit(deps.storage, &env, &config, &info.sender, amount)?;
+        clear_clawback(deps.storage, &info.sender);
+        accrue_rebate(deps.storage, &config, &info.sender, RebateAction::Stake, amount)?;
+        record_interaction(deps.storage, &env, &info.sender, Interaction::Stake)?;
+
         Ok(Response::new()
             .add_attribute("action", "stake")
             .add_attribute("staker", info.sender)
-            .add_attribute("amount", amount.to_string()))
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", STAKE_DENOM)
+            .add_attribute("new_balance", new_balance.to_string()))
     }
-    
 
-    pub fn unstake(deps: DepsMut, info: MessageInfo, amount: Uint128) -> Result<Response, ContractError> {
-        let sender = info.sender.clone();
-    
-        let current_stake = STAKES.may_load(deps.storage, &sender)?.unwrap_or(Uint128::zero());
+    /// Stakes the attached funds on behalf of `recipient`, for routers and zaps.
+    pub fn stake_for(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        recipient: String,
+        callback: Option<StakeCallback>,
+        receipt: Option<ReceiptCallback>,
+    ) -> Result<Response, ContractError> {
+        ensure_not_paused(deps.as_ref())?;
+
+        let amount = match info.funds.as_slice() {
+            [coin] if coin.denom == STAKE_DENOM && !coin.amount.is_zero() => coin.amount,
+            [] => {
+                return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
+                    "Stake amount must be greater than zero",
+                )))
+            }
+            _ => return Err(ContractError::InvalidDenom {}),
+        };
+        let recipient = deps.api.addr_validate(&recipient)?;
+        if receipt.is_some() && deps.querier.query_wasm_contract_info(&info.sender).is_err() {
+            return Err(ContractError::ReceiptNotContract {
+                address: info.sender.to_string(),
+            });
+        }
+        let config = CONFIG.load(deps.storage)?;
+        ensure_deposits_open(&env, &config)?;
+        ensure_not_contract(deps.as_ref(), &config, &recipient)?;
+        let new_balance = credit_stake(deps.storage, &env, &recipient, amount)?;
+        post(deps.storage, &[(LedgerAccount::Cash, LedgerAccount::Principal, amount)])?;
+        record_deposit(deps.storage, &env, &config, &recipient, amount)?;
+
+        let mut response = Response::new()
+            .add_attribute("action", "stake_for")
+            .add_attribute("sender", info.sender.as_str())
+            .add_attribute("staker", recipient.as_str())
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", STAKE_DENOM)
+            .add_attribute("new_balance", new_balance.to_string());
+        if let Some(callback) = callback {
+            let callee = deps.api.addr_validate(&callback.contract)?;
+            let after_stake = AfterStakeMsg {
+                staker: recipient.to_string(),
+                amount,
+                new_balance,
+                msg: callback.msg,
+            };
+            response = response.add_message(after_stake.into_cosmos_msg(callee)?);
+        }
+        if let Some(receipt) = receipt {
+            let receipt = StakeReceipt {
+                pool: env.contract.address.to_string(),
+                staker: recipient.to_string(),
+                amount,
+                denom: STAKE_DENOM.to_string(),
+                new_balance,
+                staked_since: STAKE_TRANCHES.load(deps.storage, &recipient)?[0].time,
+                msg: receipt.msg,
+            };
+            response = response.add_message(receipt.into_cosmos_msg(info.sender)?);
+        }
+        Ok(response)
+    }
+
+    /// Stakes on behalf of many recipients at once; the attached funds must cover the sum exactly.
+    pub fn batch_stake_for(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        recipients: Vec<(String, Uint128)>,
+    ) -> Result<Response, ContractError> {
+        ensure_not_paused(deps.as_ref())?;
+
+        let (credits, total) = validate_stake_batch(deps.as_ref(), &info, recipients)?;
+        let config = CONFIG.load(deps.storage)?;
+        ensure_deposits_open(&env, &config)?;
+        for (recipient, _) in &credits {
+            ensure_not_contract(deps.as_ref(), &config, recipient)?;
+        }
+        for (recipient, amount) in &credits {
+            credit_stake(deps.storage, &env, recipient, *amount)?;
+            record_deposit(deps.storage, &env, &config, recipient, *amount)?;
+        }
+        post(deps.storage, &[(LedgerAccount::Cash, LedgerAccount::Principal, total)])?;
+
+        Ok(Response::new()
+            .add_attribute("action", "batch_stake_for")
+            .add_attribute("funder", info.sender)
+            .add_attribute("recipients", credits.len().to_string())
+            .add_attribute("amount", total.to_string())
+            .add_attribute("denom", STAKE_DENOM))
+    }
     
-        if amount > current_stake {
+
+    pub fn unstake(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        amount: Uint128,
+        min_receive: Option<Uint128>,
+        recipients: Option<Vec<(String, Decimal)>>,
+    ) -> Result<Response, ContractError> {
+        ensure_can_withdraw(deps.as_ref())?;
+        let config = CONFIG.load(deps.storage)?;
+        ensure_below_commit_threshold(&config, amount)?;
+        let terms = PayoutTerms {
+            min_receive,
+            recipients: validate_recipients(deps.as_ref(), recipients)?,
+            ..Default::default()
+        };
+        if let Some(response) = queue_unconfirmed(deps.storage, &env, &info.sender, amount, &terms)? {
+            return Ok(response);
+        }
+        withdraw(deps, env, &config, info.sender, amount, terms, "unstake")
+    }
+
+    pub fn set_unstake_confirmation(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        blocks: u64,
+    ) -> Result<Response, ContractError> {
+        if deps.querier.query_wasm_contract_info(&info.sender).is_err() {
             return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
-                "Cannot unstake more than your current balance",
+                "Only contract stakers can require unstake confirmations",
             )));
         }
-    
-        let new_stake = current_stake - amount;
-        
-        if new_stake.is_zero() {
-            STAKES.remove(deps.storage, &sender);
-        } else {
-            STAKES.save(deps.storage, &sender, &new_stake)?;
+        if blocks > MAX_CONFIRMATION_BLOCKS {
+            return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(format!(
+                "Unstake confirmations wait at most {} blocks",
+                MAX_CONFIRMATION_BLOCKS
+            ))));
         }
-    
-        let bank_msg = cosmwasm_std::BankMsg::Send {
-            to_address: sender.to_string(),
-            amount: vec![cosmwasm_std::Coin {
-                denom: "token".to_string(),
-                amount,
-            }],
+        let height = env.block.height;
+        let current = confirmation_delay(deps.storage, &info.sender, height)?;
+        let confirmation = if blocks >= current {
+            UnstakeConfirmation { blocks, lowered: None }
+        } else {
+            UnstakeConfirmation {
+                blocks: current,
+                lowered: Some(LoweredDelay {
+                    blocks,
+                    from_height: checked_add_blocks("unstake_confirmation", height, current)?,
+                }),
+            }
         };
-    
+        if confirmation.blocks == 0 {
+            UNSTAKE_CONFIRMATIONS.remove(deps.storage, &info.sender);
+        } else {
+            UNSTAKE_CONFIRMATIONS.save(deps.storage, &info.sender, &confirmation)?;
+        }
+        let effective_from = confirmation.lowered.map_or(height, |lowered| lowered.from_height);
         Ok(Response::new()
-            .add_attribute("action", "unstake")
-            .add_attribute("staker", sender)
-            .add_attribute("amount", amount.to_string())
-            .add_message(bank_msg))
+            .add_attribute("action", "set_unstake_confirmation")
+            .add_attribute("staker", info.sender)
+            .add_attribute("blocks", blocks.to_string())
+            .add_attribute("effective_from", effective_from.to_string()))
     }
-    
-}
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetCount {} => to_json_binary(&query::count(deps)?),
-        QueryMsg::GetStake { address } => to_json_binary(&query::stake(deps, address)?),
+    pub fn confirm_unstake(mut deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        ensure_can_withdraw(deps.as_ref())?;
+        let pending = PENDING_UNSTAKES
+            .may_load(deps.storage, &info.sender)?
+            .ok_or(ContractError::NoPendingUnstake {})?;
+        if env.block.height < pending.confirmable_at {
+            return Err(ContractError::UnstakeNotConfirmable {
+                height: pending.confirmable_at,
+            });
+        }
+        let config = CONFIG.load(deps.storage)?;
+        let terms = PayoutTerms {
+            min_receive: pending.min_receive,
+            recipients: pending.recipients,
+            ..Default::default()
+        };
+        let response = withdraw(
+            deps.branch(),
+            env,
+            &config,
+            info.sender.clone(),
+            pending.amount,
+            terms,
+            "confirm_unstake",
+        )?;
+        PENDING_UNSTAKES.remove(deps.storage, &info.sender);
+        update_queue_depth(deps.storage, |depth| depth.pending_unstakes = depth.pending_unstakes.saturating_sub(1))?;
+        Ok(response)
     }
-}
 
-pub mod query {
-    use super::*;
+    /// Moves stake to another pool without the staker holding the funds in between. The exit
+    /// is an ordinary unstake, except that trusted pools are exempt from deposit locks and the
+    /// commit-reveal threshold.
+    pub fn migrate_stake(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        target_pool: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        ensure_can_withdraw(deps.as_ref())?;
+        // the unstake would leave without the staker's confirmation
+        if confirmation_delay(deps.storage, &info.sender, env.block.height)? > 0 {
+            return Err(ContractError::ConfirmationRequired {});
+        }
+        let config = CONFIG.load(deps.storage)?;
+        let target_pool = validate_target_pool(deps.as_ref(), &env, &target_pool)?;
+        let trusted = TRUSTED_POOLS.has(deps.storage, &target_pool);
+        if !trusted {
+            ensure_below_commit_threshold(&config, amount)?;
+        }
+        let terms = PayoutTerms {
+            migrate_to: Some(target_pool),
+            skip_locks: trusted,
+            ..Default::default()
+        };
+        withdraw(deps, env, &config, info.sender, amount, terms, "migrate_stake")
+    }
 
-    pub fn count(deps: Deps) -> StdResult<GetCountResponse> {
-        let state = STATE.load(deps.storage)?;
-        Ok(GetCountResponse { count: state.count })
+    pub fn abort_unstake(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+        let pending = PENDING_UNSTAKES
+            .may_load(deps.storage, &info.sender)?
+            .ok_or(ContractError::NoPendingUnstake {})?;
+        PENDING_UNSTAKES.remove(deps.storage, &info.sender);
+        update_queue_depth(deps.storage, |depth| depth.pending_unstakes = depth.pending_unstakes.saturating_sub(1))?;
+        Ok(Response::new()
+            .add_attribute("action", "abort_unstake")
+            .add_attribute("staker", info.sender)
+            .add_attribute("amount", pending.amount.to_string())
+            .add_attribute("denom", STAKE_DENOM))
     }
 
-    pub fn stake(deps: Deps, address: String) -> StdResult<GetStakeResponse> {
-        let addr = deps.api.addr_validate(&address)?;
-        let amount = STAKES.may_load(deps.storage, &addr)?.unwrap_or(Uint128::zero());
-        Ok(GetStakeResponse { amount })
+    /// Records the unstake for later confirmation when `staker` requires one, in which case the
+    /// returned response replaces the payout.
+    fn queue_unconfirmed(
+        storage: &mut dyn Storage,
+        env: &Env,
+        staker: &Addr,
+        amount: Uint128,
+        terms: &PayoutTerms,
+    ) -> Result<Option<Response>, ContractError> {
+        let blocks = confirmation_delay(storage, staker, env.block.height)?;
+        if blocks == 0 {
+            return Ok(None);
+        }
+        if PENDING_UNSTAKES.has(storage, staker) {
+            return Err(ContractError::UnstakePending {});
+        }
+        if amount.is_zero() {
+            return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
+                "Unstake amount must be greater than zero",
+            )));
+        }
+        if amount > STAKES.may_load(storage, staker)?.unwrap_or_default() {
+            return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
+                "Cannot unstake more than your current balance",
+            )));
+        }
+
+        let pending = PendingUnstake {
+            amount,
+            min_receive: terms.min_receive,
+            confirmable_at: checked_add_blocks("unstake_confirmation", env.block.height, blocks)?,
+            recipients: terms.recipients.clone(),
+        };
+        PENDING_UNSTAKES.save(storage, staker, &pending)?;
+        update_queue_depth(storage, |depth| depth.pending_unstakes += 1)?;
+        Ok(Some(
+            Response::new()
+                .add_attribute("action", "queue_unstake")
+                .add_attribute("staker", staker.as_str())
+                .add_attribute("amount", amount.to_string())
+                .add_attribute("denom", STAKE_DENOM)
+                .add_attribute("confirmable_at", pending.confirmable_at.to_string()),
+        ))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coins, from_json};
+    /// Commits to a large unstake without revealing its amount, so the exit can't be front-run.
+    pub fn commit_unstake(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        hash: HexBinary,
+    ) -> Result<Response, ContractError> {
+        if !STAKES.has(deps.storage, &info.sender) {
+            return Err(ContractError::NotStaker {});
+        }
+        if hash.len() != 32 {
+            return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
+                "Commit hash must be a 32-byte sha256 digest",
+            )));
+        }
+        let commit = UnstakeCommit {
+            hash,
+            height: env.block.height,
+        };
+        UNSTAKE_COMMITS.save(deps.storage, &info.sender, &commit)?;
+        record_interaction(deps.storage, &env, &info.sender, Interaction::Other)?;
+        Ok(Response::new()
+            .add_attribute("action", "commit_unstake")
+            .add_attribute("staker", info.sender)
+            .add_attribute("height", commit.height.to_string()))
+    }
 
-    #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies();
+    pub fn reveal_unstake(
+        mut deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        amount: Uint128,
+        salt: String,
+        min_receive: Option<Uint128>,
+    ) -> Result<Response, ContractError> {
+        ensure_can_withdraw(deps.as_ref())?;
+        let commit = UNSTAKE_COMMITS
+            .may_load(deps.storage, &info.sender)?
+            .ok_or(ContractError::NoUnstakeCommit {})?;
+        let config = CONFIG.load(deps.storage)?;
+        let height = revealable_at(&config, &commit);
+        if env.block.height < height {
+            return Err(ContractError::RevealTooEarly { height });
+        }
+        if unstake_commitment(amount, &salt) != commit.hash {
+            return Err(ContractError::InvalidReveal {});
+        }
+        let terms = PayoutTerms { min_receive, ..Default::default() };
+        if let Some(response) = queue_unconfirmed(deps.storage, &env, &info.sender, amount, &terms)? {
+            UNSTAKE_COMMITS.remove(deps.storage, &info.sender);
+            return Ok(response);
+        }
+        let response = withdraw(deps.branch(), env, &config, info.sender.clone(), amount, terms, "reveal_unstake")?;
+        UNSTAKE_COMMITS.remove(deps.storage, &info.sender);
+        Ok(response)
+    }
 
-        let msg = InstantiateMsg { count: 17 };
-        let info = mock_info("creator", &coins(1000, "earth"));
+    /// What a staker asked of a withdrawal's payout
+    #[derive(Default)]
+    struct PayoutTerms {
+        min_receive: Option<Uint128>,
+        recipients: Vec<(Addr, Decimal)>,
+        /// Pool the payout is staked into for the staker instead of being sent out
+        migrate_to: Option<Addr>,
+        /// Set for migrations into trusted pools, which don't wait out deposit locks
+        skip_locks: bool,
+        /// Keeper and bounty of an executed unstake order, paid in full before the fee
+        bounty: Option<(Addr, Uint128)>,
+    }
 
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+    /// Pays out `amount` of `sender`'s stake, less the exit fee, provided that leaves at least
+    /// the terms' `min_receive`.
+    fn withdraw(
+        deps: DepsMut,
+        env: Env,
+        config: &Config,
+        sender: Addr,
+        amount: Uint128,
+        terms: PayoutTerms,
+        action: &str,
+    ) -> Result<Response, ContractError> {
+        let current_stake = STAKES.may_load(deps.storage, &sender)?.unwrap_or(Uint128::zero());
+    
+        if amount.is_zero() {
+            return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
+                "Unstake amount must be greater than zero",
+            )));
+        }
+        if amount > current_stake {
+            return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
+                "Cannot unstake more than your current balance",
+            )));
+        }
 
-        // it worked, let's query the state
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: GetCountResponse = from_json(&res).unwrap();
-        assert_eq!(17, value.count);
+        if !terms.skip_locks {
+            ensure_unlocked(deps.storage, &env, config, &sender, amount, current_stake)?;
+        }
+        // payout, bounty and fee together leave the pool
+        ensure_liquidity(deps.as_ref(), &env, config, amount)?;
+        let bounty = terms.bounty.as_ref().map_or(Uint128::zero(), |(_, bounty)| *bounty);
+        // the keeper's bounty is paid in full; the fee can take at most the rest
+        let fee = exit_fee(deps.storage, config, amount)?.min(amount - bounty);
+        let payout = amount - bounty - fee;
+        if let Some(min_receive) = terms.min_receive {
+            if payout < min_receive {
+                return Err(ContractError::BelowMinReceive { payout, min_receive });
+            }
+        }
+        let alert = track_outflow(deps.storage, &env, config, amount)?;
+        let new_balance = debit_stake(deps.storage, &env, &sender, amount)?;
+        post(
+            deps.storage,
+            &[
+                (LedgerAccount::Principal, LedgerAccount::Cash, payout + bounty),
+                (LedgerAccount::Principal, LedgerAccount::FeesPayable, fee),
+                (LedgerAccount::FeesPayable, LedgerAccount::Cash, fee),
+            ],
+        )?;
+        if !fee.is_zero() {
+            LIFETIME_TOTALS.update(deps.storage, |mut totals| -> Result<_, ContractError> {
+                totals.fees = checked_add("lifetime_totals", totals.fees, fee)?;
+                Ok(totals)
+            })?;
+        }
+        clear_clawback(deps.storage, &sender);
+        // recovery exits get the principal back and nothing on top
+        if !in_recovery(deps.storage, config)? {
+            accrue_rebate(deps.storage, config, &sender, RebateAction::Unstake, amount)?;
+        }
+        record_interaction(deps.storage, &env, &sender, Interaction::Unstake)?;
+
+        let mut response = Response::new()
+            .add_attribute("action", action)
+            .add_attribute("staker", sender.as_str())
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", STAKE_DENOM)
+            .add_attribute("fee", fee.to_string())
+            .add_attribute("new_balance", new_balance.to_string())
+            .add_events(alert);
+        response = match terms.migrate_to {
+            Some(pool) => {
+                let stake_for = ExecuteMsg::StakeFor {
+                    recipient: sender.to_string(),
+                    callback: None,
+                    receipt: None,
+                };
+                response
+                    .add_attribute("target_pool", pool.as_str())
+                    .add_message(cosmwasm_std::WasmMsg::Execute {
+                        contract_addr: pool.to_string(),
+                        msg: to_json_binary(&stake_for)?,
+                        funds: vec![cosmwasm_std::Coin {
+                            denom: STAKE_DENOM.to_string(),
+                            amount: payout,
+                        }],
+                    })
+            }
+            None => response.add_messages(payout_msgs(&sender, &terms.recipients, payout)),
+        };
+        if let Some((keeper, bounty)) = terms.bounty {
+            response = response
+                .add_attribute("payout", payout.to_string())
+                .add_attribute("bounty", bounty.to_string())
+                .add_attribute("keeper", keeper.as_str());
+            if !bounty.is_zero() {
+                response = response.add_message(cosmwasm_std::BankMsg::Send {
+                    to_address: keeper.to_string(),
+                    amount: vec![cosmwasm_std::Coin {
+                        denom: STAKE_DENOM.to_string(),
+                        amount: bounty,
+                    }],
+                });
+            }
+        }
+        if !fee.is_zero() {
+            response = response.add_message(cosmwasm_std::BankMsg::Send {
+                to_address: STATE.load(deps.storage)?.owner.to_string(),
+                amount: vec![cosmwasm_std::Coin {
+                    denom: STAKE_DENOM.to_string(),
+                    amount: fee,
+                }],
+            });
+        }
+        Ok(response)
     }
 
-    #[test]
-    fn increment() {
-        let mut deps = mock_dependencies();
+    /// Records an exit to be triggered by anyone once `execute_at` passes and `condition`, if
+    /// any, holds. The stake stays in the pool until then and the balance is checked again at
+    /// execution. Conditional orders may use a past `execute_at` to be eligible right away.
+    pub fn schedule_unstake(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        amount: Uint128,
+        execute_at: Expiration,
+        bounty: Option<Uint128>,
+        condition: Option<OrderCondition>,
+    ) -> Result<Response, ContractError> {
+        if confirmation_delay(deps.storage, &info.sender, env.block.height)? > 0 {
+            return Err(ContractError::ConfirmationRequired {});
+        }
+        let bounty = bounty.unwrap_or_default();
+        let current_stake = STAKES.may_load(deps.storage, &info.sender)?.unwrap_or_default();
+        if amount.is_zero() || amount > current_stake {
+            return Err(ContractError::InvalidOrder {
+                reason: "amount must be positive and within the current stake".to_string(),
+            });
+        }
+        if bounty > amount {
+            return Err(ContractError::InvalidOrder {
+                reason: "bounty exceeds the amount".to_string(),
+            });
+        }
+        // an order publishes its amount well before it executes
+        ensure_below_commit_threshold(&CONFIG.load(deps.storage)?, amount)?;
+        if let Expiration::Never {} = execute_at {
+            return Err(ContractError::InvalidOrder {
+                reason: "execute_at must be reachable".to_string(),
+            });
+        }
+        match &condition {
+            None if execute_at.is_expired(&env.block) => {
+                return Err(ContractError::InvalidOrder {
+                    reason: "execute_at must be in the future".to_string(),
+                });
+            }
+            Some(OrderCondition::ShareAbove { share }) if share >= &Decimal::one() => {
+                return Err(ContractError::InvalidOrder {
+                    reason: "share threshold must be below 1".to_string(),
+                });
+            }
+            Some(OrderCondition::TotalStakedBelow { amount }) if amount.is_zero() => {
+                return Err(ContractError::InvalidOrder {
+                    reason: "total staked threshold must be positive".to_string(),
+                });
+            }
+            _ => {}
+        }
 
-        let msg = InstantiateMsg { count: 17 };
-        let info = mock_info("creator", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let id = NEXT_ORDER_ID.may_load(deps.storage)?.unwrap_or_default();
+        NEXT_ORDER_ID.save(deps.storage, &(id + 1))?;
+        let order = UnstakeOrder {
+            staker: info.sender.clone(),
+            amount,
+            execute_at,
+            bounty,
+            condition,
+        };
+        UNSTAKE_ORDERS.save(deps.storage, id, &order)?;
+        update_queue_depth(deps.storage, |depth| depth.unstake_orders += 1)?;
+        clear_clawback(deps.storage, &info.sender);
+        record_interaction(deps.storage, &env, &info.sender, Interaction::Other)?;
 
-        // beneficiary can release it
-        let info = mock_info("anyone", &coins(2, "token"));
-        let msg = ExecuteMsg::Increment {};
-        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        Ok(Response::new()
+            .add_attribute("action", "schedule_unstake")
+            .add_attribute("order_id", id.to_string())
+            .add_attribute("staker", info.sender)
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", STAKE_DENOM)
+            .add_attribute("execute_at", execute_at.to_string())
+            .add_attribute("bounty", bounty.to_string()))
+    }
 
-        // should increase counter by 1
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: GetCountResponse = from_json(&res).unwrap();
-        assert_eq!(18, value.count);
+    pub fn execute_unstake_order(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        ensure_can_withdraw(deps.as_ref())?;
+        let order = UNSTAKE_ORDERS
+            .may_load(deps.storage, id)?
+            .ok_or(ContractError::OrderNotFound { id })?;
+        if !order.execute_at.is_expired(&env.block) {
+            return Err(ContractError::OrderNotDue {
+                execute_at: order.execute_at,
+            });
+        }
+        // the staker may have opted into confirmations after scheduling
+        if confirmation_delay(deps.storage, &order.staker, env.block.height)? > 0 {
+            return Err(ContractError::ConfirmationRequired {});
+        }
+        let current_stake = STAKES.may_load(deps.storage, &order.staker)?.unwrap_or_default();
+        if order.amount > current_stake {
+            return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
+                "Cannot unstake more than your current balance",
+            )));
+        }
+        let total_staked = TOTAL_STAKED.load(deps.storage)?;
+        let condition_met = match &order.condition {
+            None => true,
+            Some(OrderCondition::ShareAbove { share }) => {
+                !total_staked.is_zero() && Decimal::from_ratio(current_stake, total_staked) > *share
+            }
+            Some(OrderCondition::TotalStakedBelow { amount }) => total_staked < *amount,
+        };
+        if !condition_met {
+            return Err(ContractError::OrderConditionNotMet { id });
+        }
+
+        UNSTAKE_ORDERS.remove(deps.storage, id);
+        update_queue_depth(deps.storage, |depth| depth.unstake_orders = depth.unstake_orders.saturating_sub(1))?;
+        let config = CONFIG.load(deps.storage)?;
+        // the threshold may have been lowered since the order was placed
+        ensure_below_commit_threshold(&config, order.amount)?;
+        let terms = PayoutTerms {
+            bounty: Some((info.sender, order.bounty)),
+            ..Default::default()
+        };
+        let response = withdraw(deps, env, &config, order.staker, order.amount, terms, "execute_unstake_order")?;
+        Ok(response.add_attribute("order_id", id.to_string()))
     }
 
-    #[test]
-    fn reset() {
-        let mut deps = mock_dependencies();
+    pub fn cancel_unstake_order(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let order = UNSTAKE_ORDERS
+            .may_load(deps.storage, id)?
+            .ok_or(ContractError::OrderNotFound { id })?;
+        if order.staker != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+        UNSTAKE_ORDERS.remove(deps.storage, id);
+        update_queue_depth(deps.storage, |depth| depth.unstake_orders = depth.unstake_orders.saturating_sub(1))?;
+        record_interaction(deps.storage, &env, &info.sender, Interaction::Other)?;
+        Ok(Response::new()
+            .add_attribute("action", "cancel_unstake_order")
+            .add_attribute("order_id", id.to_string())
+            .add_attribute("staker", info.sender)
+            .add_attribute("amount", order.amount.to_string()))
+    }
 
-        let msg = InstantiateMsg { count: 17 };
-        let info = mock_info("creator", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+    /// Imports stakes migrated from another pool. Each stays clawback-able until its owner interacts
+    /// with the pool, and can be swept by the owner once `confirm_within` passes unconfirmed.
+    pub fn import_stakes(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        stakes: Vec<(String, Uint128)>,
+        confirm_within: Duration,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        ensure_not_paused(deps.as_ref())?;
 
-        // beneficiary can release it
-        let unauth_info = mock_info("anyone", &coins(2, "token"));
-        let msg = ExecuteMsg::Reset { count: 5 };
-        let res = execute(deps.as_mut(), mock_env(), unauth_info, msg);
-        match res {
-            Err(ContractError::Unauthorized {}) => {}
-            _ => panic!("Must return unauthorized error"),
+        let (credits, total) = validate_stake_batch(deps.as_ref(), &info, stakes)?;
+        let deadline = confirm_within.after(&env.block);
+        for (staker, amount) in &credits {
+            credit_stake(deps.storage, &env, staker, *amount)?;
+            CLAWBACKS.update(deps.storage, staker, |clawback| -> Result<_, ContractError> {
+                let imported = clawback.map(|clawback| clawback.amount).unwrap_or_default();
+                Ok(Clawback {
+                    amount: checked_add("clawbacks", imported, *amount)?,
+                    deadline,
+                })
+            })?;
         }
+        post(deps.storage, &[(LedgerAccount::Cash, LedgerAccount::Principal, total)])?;
 
-        // only the original creator can reset the counter
-        let auth_info = mock_info("creator", &coins(2, "token"));
-        let msg = ExecuteMsg::Reset { count: 5 };
-        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        Ok(Response::new()
+            .add_attribute("action", "import_stakes")
+            .add_attribute("sender", info.sender)
+            .add_attribute("stakers", credits.len().to_string())
+            .add_attribute("amount", total.to_string())
+            .add_attribute("denom", STAKE_DENOM)
+            .add_attribute("confirm_before", deadline.to_string()))
+    }
 
-        // should now be 5
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: GetCountResponse = from_json(&res).unwrap();
-        assert_eq!(5, value.count);
+    pub fn confirm_import(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        if !clear_clawback(deps.storage, &info.sender) {
+            return Err(ContractError::NoPendingImport {});
+        }
+        record_interaction(deps.storage, &env, &info.sender, Interaction::Other)?;
+        Ok(Response::new()
+            .add_attribute("action", "confirm_import")
+            .add_attribute("staker", info.sender))
     }
 
-    #[test]
-    fn stake_tokens() {
-        let mut deps = mock_dependencies();
+    /// Sweeps an unconfirmed imported stake to the owner once its confirmation window has passed.
+    pub fn clawback_import(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        address: String,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        ensure_not_rebuilding(deps.as_ref())?;
 
-        let msg = InstantiateMsg { count: 0 };
-        let info = mock_info("creator", &coins(1000, "token"));
-        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        
-        let staker = mock_info(deps.api.addr_make("staker1").as_str(), &coins(500, "token"));
-        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
-        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
+        let staker = deps.api.addr_validate(&address)?;
+        let clawback = CLAWBACKS
+            .may_load(deps.storage, &staker)?
+            .ok_or(ContractError::NoPendingImport {})?;
+        if !clawback.deadline.is_expired(&env.block) {
+            return Err(ContractError::ClawbackNotDue {
+                deadline: clawback.deadline,
+            });
+        }
 
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStake { address: staker.sender.to_string() }).unwrap();
-        let value: GetStakeResponse = from_json(&res).unwrap();
-        assert_eq!(value.amount, Uint128::new(500));
+        let stake = STAKES.may_load(deps.storage, &staker)?.unwrap_or(Uint128::zero());
+        let amount = clawback.amount.min(stake);
+        CLAWBACKS.remove(deps.storage, &staker);
+        let mut response = Response::new()
+            .add_attribute("action", "clawback_import")
+            .add_attribute("sender", info.sender.as_str())
+            .add_attribute("staker", staker.as_str())
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", STAKE_DENOM);
+        if !amount.is_zero() {
+            let config = CONFIG.load(deps.storage)?;
+            ensure_liquidity(deps.as_ref(), &env, &config, amount)?;
+            response = response.add_events(track_outflow(deps.storage, &env, &config, amount)?);
+            let new_balance = debit_stake(deps.storage, &env, &staker, amount)?;
+            post(deps.storage, &[(LedgerAccount::Principal, LedgerAccount::Cash, amount)])?;
+            response = response
+                .add_attribute("new_balance", new_balance.to_string())
+                .add_message(cosmwasm_std::BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![cosmwasm_std::Coin {
+                    denom: STAKE_DENOM.to_string(),
+                    amount,
+                }],
+            });
+        }
+        Ok(response)
     }
 
-    #[test]
-    fn unstake_tokens() {
-        let mut deps = mock_dependencies();
-    
-        let msg = InstantiateMsg { count: 0 };
-        let info = mock_info("creator", &coins(1000, "token"));
-        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
-    
-        let staker_addr = deps.api.addr_make("staker1");  // ← Creăm o adresă Bech32 validă
-        let staker = mock_info(staker_addr.as_str(), &coins(500, "token"));
-    
-        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
-        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
-    
-        let msg = ExecuteMsg::Unstake { amount: Uint128::new(300) };
-        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
-    
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStake { address: staker_addr.to_string() }).unwrap();
-        let value: GetStakeResponse = from_json(&res).unwrap();
-        assert_eq!(value.amount, Uint128::new(200));
-    }
-    
-    #[test]
-    fn stake_without_funds_should_fail() {
-        let mut deps = mock_dependencies();
-        
-        let msg = InstantiateMsg { count: 0 };
-        let info = mock_info("creator", &coins(1000, "token"));
-        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        
-        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
-        let err = execute(deps.as_mut(), mock_env(), mock_info("staker1", &[]), msg).unwrap_err();
+    /// Permissionless solvency audit: pauses the pool if recorded stakes exceed the contract's
+    /// actual balance of the staking denom.
+    pub fn check_invariants(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        let total_staked = TOTAL_STAKED.load(deps.storage)?;
+        let balance = deps
+            .querier
+            .query_balance(&env.contract.address, STAKE_DENOM)?
+            .amount;
 
-        assert!(format!("{:?}", err).contains("Insufficient funds sent for staking"));
+        let response = Response::new()
+            .add_attribute("action", "check_invariants")
+            .add_attribute("sender", info.sender)
+            .add_attribute("total_staked", total_staked.to_string())
+            .add_attribute("balance", balance.to_string());
+
+        if total_staked <= balance {
+            return Ok(response.add_attribute("status", "ok"));
+        }
+
+        PAUSED.save(deps.storage, &true)?;
+        Ok(response.add_attribute("status", "violated").add_event(
+            Event::new("invariant_violation")
+                .add_attribute("invariant", "total_staked_backed_by_balance")
+                .add_attribute("total_staked", total_staked.to_string())
+                .add_attribute("balance", balance.to_string())
+                .add_attribute("paused", "true"),
+        ))
     }
 
-    #[test]
+    /// Owner maintenance: recomputes `kind` from the stakes map in batches. Progress is kept
+    /// between calls and stakes stay frozen until the last batch swaps the result in, so running
+    /// it again after a completed rebuild just reproduces the same aggregate.
+    pub fn rebuild(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        kind: RebuildKind,
+        limit: Option<u32>,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        if !PAUSED.load(deps.storage)? {
+            return Err(ContractError::NotPaused {});
+        }
+        let mut progress = match REBUILD.may_load(deps.storage)? {
+            Some(progress) if progress.kind != kind => {
+                return Err(ContractError::Rebuilding {
+                    kind: progress.kind.as_str().to_string(),
+                })
+            }
+            Some(progress) => progress,
+            None => {
+                if kind == RebuildKind::StakerHashes {
+                    // entries under the old salt no longer match and are skipped
+                    STAKER_HASH_SALT.save(deps.storage, &draw_staker_hash_salt(&env))?;
+                    CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+                        config.hashed_enumeration = false;
+                        Ok(config)
+                    })?;
+                }
+                RebuildProgress {
+                    kind,
+                    last_scanned: None,
+                    stakers: 0,
+                    total: Uint128::zero(),
+                    buckets: vec![],
+                }
+            }
+        };
+
+        let limit = limit.unwrap_or(DEFAULT_REBUILD_LIMIT).clamp(1, MAX_REBUILD_LIMIT) as usize;
+        let start = progress.last_scanned.as_ref().map(Bound::exclusive);
+        let mut stakes = STAKES
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit + 1)
+            .collect::<StdResult<Vec<_>>>()?;
+        let done = stakes.len() <= limit;
+        stakes.truncate(limit);
+
+        for (staker, amount) in &stakes {
+            match kind {
+                RebuildKind::TotalStaked => progress.total = checked_add("total_staked", progress.total, *amount)?,
+                RebuildKind::Distribution => {
+                    let bucket = distribution_bucket(*amount) as usize;
+                    if progress.buckets.len() <= bucket {
+                        progress.buckets.resize(bucket + 1, 0);
+                    }
+                    progress.buckets[bucket] += 1;
+                }
+                RebuildKind::StakerHashes => index_staker(deps.storage, staker, true)?,
+            }
+        }
+        progress.stakers += stakes.len() as u64;
+        progress.last_scanned = stakes.pop().map(|(addr, _)| addr);
+
+        let response = Response::new()
+            .add_attribute("action", "rebuild")
+            .add_attribute("sender", info.sender)
+            .add_attribute("kind", kind.as_str())
+            .add_attribute("stakers", progress.stakers.to_string())
+            .add_attribute("done", done.to_string());
+        if !done {
+            REBUILD.save(deps.storage, &progress)?;
+            return Ok(response);
+        }
+
+        REBUILD.remove(deps.storage);
+        match kind {
+            RebuildKind::TotalStaked => {
+                let previous = TOTAL_STAKED.may_load(deps.storage)?.unwrap_or_default();
+                TOTAL_STAKED.save(deps.storage, &progress.total)?;
+                Ok(response
+                    .add_attribute("previous", previous.to_string())
+                    .add_attribute("total_staked", progress.total.to_string()))
+            }
+            RebuildKind::Distribution => {
+                DISTRIBUTION.clear(deps.storage);
+                for (bucket, count) in progress.buckets.into_iter().enumerate() {
+                    if count > 0 {
+                        DISTRIBUTION.save(deps.storage, bucket as u8, &count)?;
+                    }
+                }
+                Ok(response)
+            }
+            RebuildKind::StakerHashes => {
+                CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+                    config.hashed_enumeration = true;
+                    Ok(config)
+                })?;
+                Ok(response.add_attribute("hashed_enumeration", "true"))
+            }
+        }
+    }
+
+    pub fn set_paused(deps: DepsMut, env: Env, info: MessageInfo, paused: bool) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        if !paused && EVACUATION.exists(deps.storage) {
+            return Err(ContractError::Evacuated {});
+        }
+        PAUSED.save(deps.storage, &paused)?;
+        if !paused {
+            // stakes may change again, so a partial rebuild would be stale
+            REBUILD.remove(deps.storage);
+        }
+        Ok(Response::new()
+            .add_attribute("action", "set_paused")
+            .add_attribute("sender", info.sender)
+            .add_attribute("paused", paused.to_string()))
+    }
+
+    /// Sets or, when every field is empty, clears the sender's own profile. Only stakers can register one.
+    pub fn set_profile(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        display_name: Option<String>,
+        url: Option<String>,
+        avatar_hash: Option<String>,
+    ) -> Result<Response, ContractError> {
+        if !STAKES.has(deps.storage, &info.sender) {
+            return Err(ContractError::NotStaker {});
+        }
+
+        let profile = Profile {
+            display_name: validate_profile_field("display_name", display_name, MAX_DISPLAY_NAME_LEN)?,
+            url: validate_profile_field("url", url, MAX_URL_LEN)?,
+            avatar_hash: validate_profile_field("avatar_hash", avatar_hash, MAX_AVATAR_HASH_LEN)?,
+        };
+        if profile == Profile::default() {
+            PROFILES.remove(deps.storage, &info.sender);
+        } else {
+            PROFILES.save(deps.storage, &info.sender, &profile)?;
+        }
+        record_interaction(deps.storage, &env, &info.sender, Interaction::Other)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_profile")
+            .add_attribute("staker", info.sender))
+    }
+
+    pub fn set_signing_key(
+        deps: DepsMut,
+        info: MessageInfo,
+        pubkey: Option<Binary>,
+    ) -> Result<Response, ContractError> {
+        match &pubkey {
+            Some(pubkey) if pubkey.len() != 33 && pubkey.len() != 65 => {
+                return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
+                    "Signing key must be a compressed or uncompressed secp256k1 public key",
+                )))
+            }
+            Some(pubkey) => SIGNING_KEYS.save(deps.storage, &info.sender, pubkey)?,
+            None => SIGNING_KEYS.remove(deps.storage, &info.sender),
+        }
+        Ok(Response::new()
+            .add_attribute("action", "set_signing_key")
+            .add_attribute("sender", info.sender)
+            .add_attribute("enabled", pubkey.is_some().to_string()))
+    }
+
+    pub fn invalidate_nonce(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+        let nonce = NONCES.may_load(deps.storage, &info.sender)?.unwrap_or_default() + 1;
+        NONCES.save(deps.storage, &info.sender, &nonce)?;
+        Ok(Response::new()
+            .add_attribute("action", "invalidate_nonce")
+            .add_attribute("sender", info.sender)
+            .add_attribute("nonce", nonce.to_string()))
+    }
+
+    /// Applies every given setting at once, validated as by its dedicated message, and reports
+    /// old and new values of what changed in a `config_update` event.
+    pub fn update_config(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        update: ConfigUpdate,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        let old = CONFIG.load(deps.storage)?;
+        let mut config = old.clone();
+
+        if let Some(tiers) = update.fee_discount_tiers {
+            validate_fee_discount_tiers(&tiers)?;
+            config.fee_discount_tiers = tiers;
+        }
+        if let Some(tiers) = update.exit_fee_tiers {
+            validate_exit_fee_tiers(&tiers)?;
+            config.exit_fee_tiers = tiers;
+        }
+        if let Some(reject) = update.reject_contract_stakers {
+            config.reject_contract_stakers = reject;
+        }
+        if let Some(asset_metadata) = update.asset_metadata {
+            config.asset_metadata = Some(asset_metadata);
+        }
+        if let Some(alert) = update.tvl_alert {
+            validate_tvl_alert(&alert)?;
+            config.tvl_alert = Some(alert);
+        }
+        if let Some(address) = update.escrow {
+            if EVACUATION.exists(deps.storage) {
+                return Err(ContractError::Evacuated {});
+            }
+            config.escrow = Some(validate_escrow(deps.as_ref(), &address)?);
+        }
+        if let Some(enabled) = update.recovery_mode {
+            config.recovery_mode = enabled;
+        }
+        if let Some(rates) = update.rebate_rates {
+            config.rebate_rates = Some(rates);
+        }
+        if let Some(close_at) = update.deposits_close_at {
+            validate_deposits_close_at(&env, &old, close_at)?;
+            config.deposits_close_at = Some(close_at);
+        }
+        if let Some(rule) = update.commit_reveal {
+            validate_commit_reveal(&rule)?;
+            config.commit_reveal = Some(rule);
+        }
+        if let Some(blocks) = update.deposit_lock_blocks {
+            validate_deposit_lock_blocks(blocks)?;
+            config.deposit_lock_blocks = blocks;
+        }
+        if let Some(metadata) = update.pool_metadata {
+            validate_pool_metadata(&metadata)?;
+            config.pool_metadata = Some(metadata);
+        }
+        if let Some(enabled) = update.liquidity_check {
+            config.liquidity_check = enabled;
+        }
+        match update.hashed_enumeration {
+            Some(true) if !config.hashed_enumeration => {
+                return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
+                    "Hashed enumeration is turned on by Rebuild { kind: staker_hashes }",
+                )));
+            }
+            Some(false) => {
+                config.hashed_enumeration = false;
+                STAKER_HASH_SALT.remove(deps.storage);
+            }
+            _ => {}
+        }
+
+        if config.tvl_alert != old.tvl_alert {
+            TVL_WINDOW.remove(deps.storage);
+        }
+        CONFIG.save(deps.storage, &config)?;
+
+        let diff = config_diff(&old, &config)?;
+        Ok(Response::new()
+            .add_attribute("action", "update_config")
+            .add_attribute("sender", info.sender)
+            .add_attribute("changed", (diff.attributes.len() / 2).to_string())
+            .add_event(diff))
+    }
+
+    pub fn set_fee_discount_tiers(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        tiers: Vec<FeeDiscountTier>,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        validate_fee_discount_tiers(&tiers)?;
+
+        let tier_count = tiers.len();
+        CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+            config.fee_discount_tiers = tiers;
+            Ok(config)
+        })?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_fee_discount_tiers")
+            .add_attribute("sender", info.sender)
+            .add_attribute("tiers", tier_count.to_string()))
+    }
+
+    pub fn set_exit_fee_tiers(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        tiers: Vec<ExitFeeTier>,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        validate_exit_fee_tiers(&tiers)?;
+
+        let tier_count = tiers.len();
+        CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+            config.exit_fee_tiers = tiers;
+            Ok(config)
+        })?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_exit_fee_tiers")
+            .add_attribute("sender", info.sender)
+            .add_attribute("tiers", tier_count.to_string()))
+    }
+
+    pub fn set_reject_contract_stakers(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        reject: bool,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+            config.reject_contract_stakers = reject;
+            Ok(config)
+        })?;
+        Ok(Response::new()
+            .add_attribute("action", "set_reject_contract_stakers")
+            .add_attribute("sender", info.sender)
+            .add_attribute("reject", reject.to_string()))
+    }
+
+    pub fn set_tvl_alert(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        alert: Option<TvlAlert>,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        if let Some(alert) = &alert {
+            validate_tvl_alert(alert)?;
+        }
+        CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+            config.tvl_alert = alert.clone();
+            Ok(config)
+        })?;
+        TVL_WINDOW.remove(deps.storage);
+        Ok(Response::new()
+            .add_attribute("action", "set_tvl_alert")
+            .add_attribute("sender", info.sender)
+            .add_attribute("enabled", alert.is_some().to_string()))
+    }
+
+    pub fn set_commit_reveal(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        rule: Option<CommitReveal>,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        if let Some(rule) = &rule {
+            validate_commit_reveal(rule)?;
+        }
+        CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+            config.commit_reveal = rule.clone();
+            Ok(config)
+        })?;
+        Ok(Response::new()
+            .add_attribute("action", "set_commit_reveal")
+            .add_attribute("sender", info.sender)
+            .add_attribute("enabled", rule.is_some().to_string()))
+    }
+
+    pub fn set_deposit_lock_blocks(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        blocks: u64,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        validate_deposit_lock_blocks(blocks)?;
+        CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+            config.deposit_lock_blocks = blocks;
+            Ok(config)
+        })?;
+        Ok(Response::new()
+            .add_attribute("action", "set_deposit_lock_blocks")
+            .add_attribute("sender", info.sender)
+            .add_attribute("blocks", blocks.to_string()))
+    }
+
+    pub fn set_recovery_mode(deps: DepsMut, env: Env, info: MessageInfo, enabled: bool) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+            config.recovery_mode = enabled;
+            Ok(config)
+        })?;
+        Ok(Response::new()
+            .add_attribute("action", "set_recovery_mode")
+            .add_attribute("sender", info.sender)
+            .add_attribute("enabled", enabled.to_string()))
+    }
+
+    pub fn set_deposits_close_at(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        close_at: Expiration,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        let mut config = CONFIG.load(deps.storage)?;
+        validate_deposits_close_at(&env, &config, close_at)?;
+        config.deposits_close_at = Some(close_at);
+        CONFIG.save(deps.storage, &config)?;
+        Ok(Response::new()
+            .add_attribute("action", "set_deposits_close_at")
+            .add_attribute("sender", info.sender)
+            .add_attribute("close_at", close_at.to_string()))
+    }
+
+    pub fn set_pool_metadata(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        metadata: Option<PoolMetadata>,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        if let Some(metadata) = &metadata {
+            validate_pool_metadata(metadata)?;
+        }
+        CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+            config.pool_metadata = metadata.clone();
+            Ok(config)
+        })?;
+        Ok(Response::new()
+            .add_attribute("action", "set_pool_metadata")
+            .add_attribute("sender", info.sender)
+            .add_attribute("enabled", metadata.is_some().to_string()))
+    }
+
+    pub fn set_rebate_rates(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        rates: Option<RebateRates>,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+            config.rebate_rates = rates.clone();
+            Ok(config)
+        })?;
+        Ok(Response::new()
+            .add_attribute("action", "set_rebate_rates")
+            .add_attribute("sender", info.sender)
+            .add_attribute("enabled", rates.is_some().to_string()))
+    }
+
+    pub fn fund_rebates(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        let amount = match info.funds.as_slice() {
+            [coin] if coin.denom == STAKE_DENOM && !coin.amount.is_zero() => coin.amount,
+            _ => return Err(ContractError::InvalidDenom {}),
+        };
+        let pool = REBATE_POOL.may_load(deps.storage)?.unwrap_or_default();
+        let pool = checked_add("rebate_pool", pool, amount)?;
+        REBATE_POOL.save(deps.storage, &pool)?;
+        post(deps.storage, &[(LedgerAccount::Cash, LedgerAccount::RewardsPayable, amount)])?;
+        Ok(Response::new()
+            .add_attribute("action", "fund_rebates")
+            .add_attribute("sender", info.sender)
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", STAKE_DENOM)
+            .add_attribute("pool", pool.to_string()))
+    }
+
+    pub fn claim_rebates(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        recipients: Option<Vec<(String, Decimal)>>,
+    ) -> Result<Response, ContractError> {
+        let recipients = validate_recipients(deps.as_ref(), recipients)?;
+        let mut account = REBATE_ACCOUNTS
+            .may_load(deps.storage, &info.sender)?
+            .unwrap_or_default();
+        if account.accrued.is_zero() {
+            return Err(ContractError::NoRebates {});
+        }
+        let amount = std::mem::take(&mut account.accrued);
+        ensure_liquidity(deps.as_ref(), &env, &CONFIG.load(deps.storage)?, amount)?;
+        REBATE_ACCOUNTS.save(deps.storage, &info.sender, &account)?;
+        post(deps.storage, &[(LedgerAccount::RewardsPayable, LedgerAccount::Cash, amount)])?;
+        record_interaction(deps.storage, &env, &info.sender, Interaction::Claim)?;
+        Ok(Response::new()
+            .add_attribute("action", "claim_rebates")
+            .add_attribute("staker", info.sender.as_str())
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", STAKE_DENOM)
+            .add_messages(payout_msgs(&info.sender, &recipients, amount)))
+    }
+
+    /// Testnet-only: sends a small amount of the staking denom to the caller, at most once per
+    /// cooldown. Only funds above the staked principal and the rebates still payable, funded or
+    /// already accrued, are handed out.
+    #[cfg(feature = "testnet")]
+    pub fn faucet(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        use crate::state::FAUCET_CLAIMS;
+
+        if let Some(available_at) = FAUCET_CLAIMS.may_load(deps.storage, &info.sender)? {
+            if !available_at.is_expired(&env.block) {
+                return Err(ContractError::FaucetCooldown { available_at });
+            }
+        }
+        let balance = deps
+            .querier
+            .query_balance(&env.contract.address, STAKE_DENOM)?
+            .amount;
+        // the ledger credits rewards payable with both the unallocated pool and accrued rebates
+        let payable = LEDGER.may_load(deps.storage)?.unwrap_or_default().rewards_payable;
+        let payable = Uint128::try_from(payable.unsigned_abs())
+            .map_err(|_| ContractError::Overflow { ledger: "ledger".to_string() })?;
+        let reserved = checked_add("faucet", TOTAL_STAKED.load(deps.storage)?, payable)?;
+        let amount = Uint128::new(FAUCET_AMOUNT);
+        if balance.saturating_sub(reserved) < amount {
+            return Err(ContractError::FaucetEmpty {});
+        }
+        FAUCET_CLAIMS.save(deps.storage, &info.sender, &FAUCET_COOLDOWN.after(&env.block))?;
+        post(deps.storage, &[(LedgerAccount::Treasury, LedgerAccount::Cash, amount)])?;
+        Ok(Response::new()
+            .add_attribute("action", "faucet")
+            .add_attribute("recipient", info.sender.as_str())
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", STAKE_DENOM)
+            .add_message(cosmwasm_std::BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![cosmwasm_std::Coin {
+                    denom: STAKE_DENOM.to_string(),
+                    amount,
+                }],
+            }))
+    }
+
+    /// Pre-registers the recovery contract used by `EmergencyEvacuate`, or clears it.
+    pub fn set_escrow(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        address: Option<String>,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        if EVACUATION.exists(deps.storage) {
+            return Err(ContractError::Evacuated {});
+        }
+        let escrow = address.map(|address| validate_escrow(deps.as_ref(), &address)).transpose()?;
+        CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+            config.escrow = escrow.clone();
+            Ok(config)
+        })?;
+        Ok(Response::new()
+            .add_attribute("action", "set_escrow")
+            .add_attribute("sender", info.sender)
+            .add_attribute("escrow", escrow.map(String::from).unwrap_or_default()))
+    }
+
+    pub fn set_guardian(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        guardian: Option<NewGuardian>,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        let response = Response::new()
+            .add_attribute("action", "set_guardian")
+            .add_attribute("sender", info.sender.clone());
+        let Some(guardian) = guardian else {
+            GUARDIAN.remove(deps.storage);
+            return Ok(response.add_attribute("guardian", ""));
+        };
+
+        let address = deps.api.addr_validate(&guardian.address)?;
+        if address == info.sender {
+            return Err(ContractError::InvalidGuardian {
+                reason: "the owner can't be its own guardian".to_string(),
+            });
+        }
+        if matches!(guardian.inactivity, Duration::Time(0) | Duration::Height(0)) {
+            return Err(ContractError::InvalidGuardian {
+                reason: "inactivity must be positive".to_string(),
+            });
+        }
+        let too_long = match guardian.inactivity {
+            Duration::Height(blocks) => blocks > MAX_GUARDIAN_INACTIVITY_BLOCKS,
+            Duration::Time(seconds) => seconds > MAX_GUARDIAN_INACTIVITY_SECONDS,
+        };
+        if too_long {
+            return Err(ContractError::InvalidGuardian {
+                reason: "inactivity must be at most about ten years".to_string(),
+            });
+        }
+        GUARDIAN.save(
+            deps.storage,
+            &Guardian {
+                address: address.clone(),
+                inactivity: guardian.inactivity,
+            },
+        )?;
+        Ok(response.add_attribute("guardian", address))
+    }
+
+    /// The guardian takes over a pool whose owner went silent. The guardian role is used up in
+    /// the process; the new owner may name a fresh one.
+    pub fn claim_ownership(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        let guardian = GUARDIAN.may_load(deps.storage)?.ok_or(ContractError::Unauthorized {})?;
+        if info.sender != guardian.address {
+            return Err(ContractError::Unauthorized {});
+        }
+        let heartbeat = OWNER_HEARTBEAT.load(deps.storage)?;
+        let claimable_at = claimable_at(&guardian, &heartbeat);
+        if !claimable_at.is_expired(&env.block) {
+            return Err(ContractError::OwnerActive { claimable_at });
+        }
+
+        let mut state = STATE.load(deps.storage)?;
+        let previous_owner = std::mem::replace(&mut state.owner, guardian.address);
+        STATE.save(deps.storage, &state)?;
+        GUARDIAN.remove(deps.storage);
+        // the claim counts as the new owner's first heartbeat
+        ensure_owner(deps.storage, &env, &info)?;
+        Ok(Response::new()
+            .add_attribute("action", "claim_ownership")
+            .add_attribute("sender", info.sender)
+            .add_attribute("previous_owner", previous_owner))
+    }
+
+    pub fn add_trusted_pool(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        address: String,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        let pool = validate_target_pool(deps.as_ref(), &env, &address)?;
+        if TRUSTED_POOLS.has(deps.storage, &pool) {
+            return Err(ContractError::TrustedPoolExists {
+                address: pool.to_string(),
+            });
+        }
+        TRUSTED_POOLS.save(deps.storage, &pool, &env.block.height)?;
+        Ok(Response::new()
+            .add_attribute("action", "add_trusted_pool")
+            .add_attribute("sender", info.sender)
+            .add_event(Event::new("trusted_pool_added").add_attribute("pool", pool.as_str())))
+    }
+
+    pub fn remove_trusted_pool(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        address: String,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        let pool = deps.api.addr_validate(&address)?;
+        if !TRUSTED_POOLS.has(deps.storage, &pool) {
+            return Err(ContractError::UnknownTrustedPool {
+                address: pool.to_string(),
+            });
+        }
+        TRUSTED_POOLS.remove(deps.storage, &pool);
+        Ok(Response::new()
+            .add_attribute("action", "remove_trusted_pool")
+            .add_attribute("sender", info.sender)
+            .add_event(Event::new("trusted_pool_removed").add_attribute("pool", pool.as_str())))
+    }
+
+    /// Incident response: sends every liquid token to the registered escrow and freezes the pool.
+    /// Stakes stay recorded so the escrow can reconcile refunds against `Stake` queries.
+    pub fn emergency_evacuate(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &env, &info)?;
+        if EVACUATION.exists(deps.storage) {
+            return Err(ContractError::Evacuated {});
+        }
+        let escrow = CONFIG.load(deps.storage)?.escrow.ok_or(ContractError::NoEscrow {})?;
+        let balance = deps
+            .querier
+            .query_balance(&env.contract.address, STAKE_DENOM)?;
+
+        PAUSED.save(deps.storage, &true)?;
+        EVACUATION.save(
+            deps.storage,
+            &Evacuation {
+                escrow: escrow.clone(),
+                amount: balance.amount,
+                time: env.block.time,
+            },
+        )?;
+        post(deps.storage, &[(LedgerAccount::Treasury, LedgerAccount::Cash, balance.amount)])?;
+
+        let mut response = Response::new()
+            .add_attribute("action", "emergency_evacuate")
+            .add_attribute("sender", info.sender)
+            .add_attribute("escrow", escrow.as_str())
+            .add_attribute("amount", balance.amount.to_string())
+            .add_attribute("denom", STAKE_DENOM);
+        if !balance.amount.is_zero() {
+            response = response.add_message(cosmwasm_std::BankMsg::Send {
+                to_address: escrow.to_string(),
+                amount: vec![balance],
+            });
+        }
+        Ok(response)
+    }
+
+    fn validate_profile_field(
+        field: &str,
+        value: Option<String>,
+        max_len: usize,
+    ) -> Result<Option<String>, ContractError> {
+        match value {
+            Some(value) if value.len() > max_len => Err(ContractError::InvalidProfile {
+                reason: format!("{} exceeds {} bytes", field, max_len),
+            }),
+            Some(value) if value.is_empty() => Ok(None),
+            value => Ok(value),
+        }
+    }
+}
+
+/// Validates (address, amount) pairs against the attached funds, which must cover the sum exactly.
+fn validate_stake_batch(
+    deps: Deps,
+    info: &MessageInfo,
+    recipients: Vec<(String, Uint128)>,
+) -> Result<(Vec<(Addr, Uint128)>, Uint128), ContractError> {
+    let mut total = Uint128::zero();
+    let mut credits = Vec::with_capacity(recipients.len());
+    for (recipient, amount) in recipients {
+        if amount.is_zero() {
+            return Err(ContractError::Std(cosmwasm_std::StdError::generic_err(
+                "Stake amount must be greater than zero",
+            )));
+        }
+        total = checked_add("batch_total", total, amount)?;
+        credits.push((deps.api.addr_validate(&recipient)?, amount));
+    }
+
+    let received = match info.funds.as_slice() {
+        [coin] if coin.denom == STAKE_DENOM => coin.amount,
+        [] => Uint128::zero(),
+        _ => return Err(ContractError::InvalidDenom {}),
+    };
+    if received != total {
+        return Err(ContractError::FundsMismatch {
+            expected: total,
+            received,
+        });
+    }
+    Ok((credits, total))
+}
+
+fn validate_fee_discount_tiers(tiers: &[FeeDiscountTier]) -> Result<(), ContractError> {
+    for (i, tier) in tiers.iter().enumerate() {
+        if tier.discount > Decimal::one() {
+            return Err(ContractError::InvalidFeeDiscountTiers {
+                reason: "discount cannot exceed 100%".to_string(),
+            });
+        }
+        if i > 0 && tier.min_stake <= tiers[i - 1].min_stake {
+            return Err(ContractError::InvalidFeeDiscountTiers {
+                reason: "tiers must be sorted by strictly increasing min_stake".to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn validate_exit_fee_tiers(tiers: &[ExitFeeTier]) -> Result<(), ContractError> {
+    for (i, tier) in tiers.iter().enumerate() {
+        // a 100% fee would leave nothing to pay out
+        if tier.fee >= Decimal::one() || tier.min_share > Decimal::one() {
+            return Err(ContractError::InvalidExitFeeTiers {
+                reason: "fee must be below 100% and min_share cannot exceed 100%".to_string(),
+            });
+        }
+        if i > 0 && (tier.min_share <= tiers[i - 1].min_share || tier.fee < tiers[i - 1].fee) {
+            return Err(ContractError::InvalidExitFeeTiers {
+                reason: "tiers must be sorted by strictly increasing min_share, with non-decreasing fees"
+                    .to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn validate_tvl_alert(alert: &TvlAlert) -> Result<(), ContractError> {
+    if matches!(alert.window, Duration::Time(0) | Duration::Height(0)) {
+        return Err(ContractError::InvalidTvlAlert {
+            reason: "window must be positive".to_string(),
+        });
+    }
+    if alert.max_drop.is_zero() || alert.max_drop > Decimal::one() {
+        return Err(ContractError::InvalidTvlAlert {
+            reason: "max_drop must be in (0, 1]".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_commit_reveal(rule: &CommitReveal) -> Result<(), ContractError> {
+    if rule.reveal_delay == 0 {
+        return Err(ContractError::InvalidCommitReveal {
+            reason: "reveal_delay must be at least one block".to_string(),
+        });
+    }
+    if rule.reveal_delay > MAX_REVEAL_DELAY_BLOCKS {
+        return Err(ContractError::InvalidCommitReveal {
+            reason: format!("reveal_delay must be at most {} blocks", MAX_REVEAL_DELAY_BLOCKS),
+        });
+    }
+    Ok(())
+}
+
+fn validate_deposit_lock_blocks(blocks: u64) -> Result<(), ContractError> {
+    if blocks > MAX_DEPOSIT_LOCK_BLOCKS {
+        return Err(ContractError::InvalidDepositLock {
+            reason: format!("blocks must be at most {}", MAX_DEPOSIT_LOCK_BLOCKS),
+        });
+    }
+    Ok(())
+}
+
+/// A deadline can be set while there is none, or extended before it passes.
+fn validate_deposits_close_at(env: &Env, config: &Config, close_at: Expiration) -> Result<(), ContractError> {
+    if let Expiration::Never {} = close_at {
+        return Err(ContractError::InvalidDepositsCloseAt {
+            reason: "close_at must be reachable".to_string(),
+        });
+    }
+    if close_at.is_expired(&env.block) {
+        return Err(ContractError::InvalidDepositsCloseAt {
+            reason: "close_at must be in the future".to_string(),
+        });
+    }
+    if let Some(current) = config.deposits_close_at {
+        ensure_deposits_open(env, config)?;
+        // heights and times don't compare, so an extension must keep the unit
+        if close_at.partial_cmp(&current) != Some(std::cmp::Ordering::Greater) {
+            return Err(ContractError::InvalidDepositsCloseAt {
+                reason: format!("close_at can only extend the current deadline {current}"),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn validate_pool_metadata(metadata: &PoolMetadata) -> Result<(), ContractError> {
+    let fields = [
+        ("name", Some(&metadata.name), MAX_POOL_NAME_LEN),
+        ("description", metadata.description.as_ref(), MAX_POOL_DESCRIPTION_LEN),
+        ("website", metadata.website.as_ref(), MAX_URL_LEN),
+        ("logo_uri", metadata.logo_uri.as_ref(), MAX_URL_LEN),
+    ];
+    for (field, value, max_len) in fields {
+        match value {
+            Some(value) if value.is_empty() => {
+                return Err(ContractError::InvalidPoolMetadata {
+                    reason: format!("{} is empty", field),
+                })
+            }
+            Some(value) if value.len() > max_len => {
+                return Err(ContractError::InvalidPoolMetadata {
+                    reason: format!("{} exceeds {} bytes", field, max_len),
+                })
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// An unset split pays the staker alone; a given one needs distinct, valid addresses with
+/// non-zero shares adding up to exactly one.
+fn validate_recipients(
+    deps: Deps,
+    recipients: Option<Vec<(String, Decimal)>>,
+) -> Result<Vec<(Addr, Decimal)>, ContractError> {
+    let Some(recipients) = recipients else {
+        return Ok(vec![]);
+    };
+    let invalid = |reason: &str| ContractError::InvalidRecipients {
+        reason: reason.to_string(),
+    };
+    if recipients.is_empty() {
+        return Err(invalid("no recipients given"));
+    }
+    if recipients.len() > MAX_PAYOUT_RECIPIENTS {
+        return Err(ContractError::InvalidRecipients {
+            reason: format!("more than {} recipients", MAX_PAYOUT_RECIPIENTS),
+        });
+    }
+    let mut validated: Vec<(Addr, Decimal)> = Vec::with_capacity(recipients.len());
+    let mut total = Decimal::zero();
+    for (address, share) in recipients {
+        let address = deps.api.addr_validate(&address)?;
+        if share.is_zero() {
+            return Err(invalid("shares must be positive"));
+        }
+        if validated.iter().any(|(other, _)| *other == address) {
+            return Err(ContractError::InvalidRecipients {
+                reason: format!("{} is listed twice", address),
+            });
+        }
+        total = total.checked_add(share).map_err(|_| invalid("shares add up to more than one"))?;
+        validated.push((address, share));
+    }
+    if total != Decimal::one() {
+        return Err(invalid("shares must add up to one"));
+    }
+    Ok(validated)
+}
+
+/// Sends `amount` to `staker`, or splits it by `recipients`' shares when given. Each share is
+/// rounded down and the first recipient gets the remainder, so the parts add up to `amount`.
+fn payout_msgs(staker: &Addr, recipients: &[(Addr, Decimal)], amount: Uint128) -> Vec<cosmwasm_std::BankMsg> {
+    let send = |to_address: &Addr, amount: Uint128| cosmwasm_std::BankMsg::Send {
+        to_address: to_address.to_string(),
+        amount: vec![cosmwasm_std::Coin {
+            denom: STAKE_DENOM.to_string(),
+            amount,
+        }],
+    };
+    let Some(((first, _), rest)) = recipients.split_first() else {
+        return vec![send(staker, amount)];
+    };
+    let parts: Vec<_> = rest.iter().map(|(address, share)| (address, amount.mul_floor(*share))).collect();
+    let remainder = amount - parts.iter().map(|(_, part)| *part).sum::<Uint128>();
+    std::iter::once((first, remainder))
+        .chain(parts)
+        .filter(|(_, part)| !part.is_zero())
+        .map(|(address, part)| send(address, part))
+        .collect()
+}
+
+/// Only other deployments of this contract, as recorded in their cw2 info, take migrated stake.
+fn validate_target_pool(deps: Deps, env: &Env, address: &str) -> Result<Addr, ContractError> {
+    let pool = deps.api.addr_validate(address)?;
+    let is_pool = cw2::query_contract_info(&deps.querier, &pool).is_ok_and(|info| info.contract == CONTRACT_NAME);
+    if pool == env.contract.address || !is_pool {
+        return Err(ContractError::InvalidTargetPool {
+            address: pool.to_string(),
+        });
+    }
+    Ok(pool)
+}
+
+/// Up to `limit` records of `map` between `min` and `max`, in ascending order. Records whose key
+/// or value no longer deserializes are skipped and returned separately instead of failing the
+/// whole query.
+fn range_lenient<'a, K, V>(
+    storage: &dyn Storage,
+    map: &Map<K, V>,
+    min: Option<Bound<'a, K>>,
+    max: Option<Bound<'a, K>>,
+    limit: usize,
+) -> (Vec<(K::Output, V)>, Vec<MalformedRecord>)
+where
+    K: PrimaryKey<'a> + KeyDeserialize,
+    V: Serialize + DeserializeOwned,
+{
+    let prefix = to_length_prefixed(map.namespace_bytes());
+    let min = min.map(|bound| bound.to_raw_bound());
+    let max = max.map(|bound| bound.to_raw_bound());
+    let mut records = vec![];
+    let mut malformed = vec![];
+    for (key, value) in range_with_prefix(storage, &prefix, min, max, Order::Ascending).take(limit) {
+        match K::from_vec(key.clone()).and_then(|k| Ok((k, from_json(&value)?))) {
+            Ok(record) => records.push(record),
+            Err(err) => malformed.push(MalformedRecord {
+                key: key.into(),
+                error: err.to_string(),
+            }),
+        }
+    }
+    (records, malformed)
+}
+
+fn validate_escrow(deps: Deps, address: &str) -> Result<Addr, ContractError> {
+    let escrow = deps.api.addr_validate(address)?;
+    if deps.querier.query_wasm_contract_info(&escrow).is_err() {
+        return Err(ContractError::InvalidEscrow {
+            address: escrow.to_string(),
+        });
+    }
+    Ok(escrow)
+}
+
+/// `<field>_old` and `<field>_new` attributes, as JSON, for every setting that differs.
+fn config_diff(old: &Config, new: &Config) -> StdResult<Event> {
+    fn diff<T>(event: Event, field: &str, old: &T, new: &T) -> StdResult<Event>
+    where
+        T: serde::Serialize + PartialEq,
+    {
+        if old == new {
+            return Ok(event);
+        }
+        Ok(event
+            .add_attribute(format!("{field}_old"), to_json_string(old)?)
+            .add_attribute(format!("{field}_new"), to_json_string(new)?))
+    }
+
+    let event = Event::new("config_update");
+    let event = diff(event, "fee_discount_tiers", &old.fee_discount_tiers, &new.fee_discount_tiers)?;
+    let event = diff(event, "exit_fee_tiers", &old.exit_fee_tiers, &new.exit_fee_tiers)?;
+    let event = diff(
+        event,
+        "reject_contract_stakers",
+        &old.reject_contract_stakers,
+        &new.reject_contract_stakers,
+    )?;
+    let event = diff(event, "asset_metadata", &old.asset_metadata, &new.asset_metadata)?;
+    let event = diff(event, "tvl_alert", &old.tvl_alert, &new.tvl_alert)?;
+    let event = diff(event, "escrow", &old.escrow, &new.escrow)?;
+    let event = diff(event, "recovery_mode", &old.recovery_mode, &new.recovery_mode)?;
+    let event = diff(event, "rebate_rates", &old.rebate_rates, &new.rebate_rates)?;
+    let event = diff(event, "deposits_close_at", &old.deposits_close_at, &new.deposits_close_at)?;
+    let event = diff(event, "commit_reveal", &old.commit_reveal, &new.commit_reveal)?;
+    let event = diff(event, "deposit_lock_blocks", &old.deposit_lock_blocks, &new.deposit_lock_blocks)?;
+    let event = diff(event, "pool_metadata", &old.pool_metadata, &new.pool_metadata)?;
+    let event = diff(event, "liquidity_check", &old.liquidity_check, &new.liquidity_check)?;
+    diff(event, "hashed_enumeration", &old.hashed_enumeration, &new.hashed_enumeration)
+}
+
+/// Salt for staker hashes, from the block and transaction that start the index. It is derived
+/// from public data, so it only keeps hashes from lining up across indexes and pools.
+fn draw_staker_hash_salt(env: &Env) -> Binary {
+    let tx_index = env.transaction.as_ref().map_or(0, |tx| tx.index);
+    let digest = Sha256::new()
+        .chain_update(to_length_prefixed(env.block.chain_id.as_bytes()))
+        .chain_update(to_length_prefixed(env.contract.address.as_bytes()))
+        .chain_update(env.block.height.to_be_bytes())
+        .chain_update(env.block.time.nanos().to_be_bytes())
+        .chain_update(tx_index.to_be_bytes())
+        .finalize();
+    Binary::from(digest.as_slice())
+}
+
+fn staker_hash(salt: &[u8], staker: &Addr) -> Vec<u8> {
+    Sha256::new()
+        .chain_update(salt)
+        .chain_update(staker.as_bytes())
+        .finalize()
+        .to_vec()
+}
+
+/// Adds `staker` to, or drops them from, the index behind `HashedStakes` while one is kept.
+fn index_staker(storage: &mut dyn Storage, staker: &Addr, staked: bool) -> StdResult<()> {
+    let Some(salt) = STAKER_HASH_SALT.may_load(storage)? else {
+        return Ok(());
+    };
+    let hash = staker_hash(&salt, staker);
+    if staked {
+        STAKER_HASHES.save(storage, &hash, staker)
+    } else {
+        STAKER_HASHES.remove(storage, &hash);
+        Ok(())
+    }
+}
+
+/// A page of the staker hash index
+struct HashedScan {
+    /// Hash, address and stake of each current staker
+    stakers: Vec<(Vec<u8>, Addr, Uint128)>,
+    malformed: Vec<MalformedRecord>,
+    /// Last hash scanned, unless the index ended within the page
+    last_scanned: Option<Vec<u8>>,
+}
+
+/// Up to `limit` entries of the hash index after `start_after`.
+fn scan_staker_hashes(deps: Deps, start_after: Option<&[u8]>, limit: usize) -> StdResult<HashedScan> {
+    let salt = STAKER_HASH_SALT.load(deps.storage)?;
+    let (scanned, mut malformed) =
+        range_lenient(deps.storage, &STAKER_HASHES, start_after.map(Bound::exclusive), None, limit);
+    let last_scanned = if scanned.len() + malformed.len() == limit {
+        let last_read = scanned.last().map(|(hash, _)| hash.clone());
+        let last_malformed = malformed.last().map(|record| record.key.to_vec());
+        last_read.max(last_malformed)
+    } else {
+        None
+    };
+    let mut stakers = vec![];
+    for (hash, staker) in scanned {
+        // left behind under an earlier salt
+        if staker_hash(&salt, &staker) != hash {
+            continue;
+        }
+        match STAKES.may_load(deps.storage, &staker) {
+            Ok(Some(stake)) => stakers.push((hash, staker, stake)),
+            Ok(None) => {}
+            Err(err) => malformed.push(MalformedRecord {
+                key: hash.into(),
+                error: err.to_string(),
+            }),
+        }
+    }
+    Ok(HashedScan {
+        stakers,
+        malformed,
+        last_scanned,
+    })
+}
+
+/// Adds `amount` to `staker`'s balance and every aggregate derived from it.
+fn credit_stake(
+    storage: &mut dyn Storage,
+    env: &Env,
+    staker: &Addr,
+    amount: Uint128,
+) -> Result<Uint128, ContractError> {
+    let current_stake = STAKES.may_load(storage, staker)?.unwrap_or(Uint128::zero());
+    let new_stake = checked_add("stakes", current_stake, amount)?;
+    if current_stake.is_zero() {
+        index_staker(storage, staker, true)?;
+    }
+    STAKES.save(storage, staker, &new_stake)?;
+    let mut tranches = STAKE_TRANCHES.may_load(storage, staker)?.unwrap_or_default();
+    let full = tranches.len() >= MAX_STAKE_TRANCHES;
+    match tranches.last_mut() {
+        // joining the newest tranche restarts its age, never that of older ones
+        Some(newest) if newest.height == env.block.height || full => {
+            newest.amount = checked_add("stake_tranches", newest.amount, amount)?;
+            newest.height = env.block.height;
+            newest.time = env.block.time;
+        }
+        _ => tranches.push(StakeTranche {
+            amount,
+            height: env.block.height,
+            time: env.block.time,
+        }),
+    }
+    STAKE_TRANCHES.save(storage, staker, &tranches)?;
+    update_distribution(storage, current_stake, new_stake)?;
+    TOTAL_STAKED.update(storage, |total| checked_add("total_staked", total, amount))?;
+    LIFETIME_TOTALS.update(storage, |mut totals| -> Result<_, ContractError> {
+        totals.staked = checked_add("lifetime_totals", totals.staked, amount)?;
+        Ok(totals)
+    })?;
+    record_daily_activity(storage, env, staker, amount, Uint128::zero())?;
+    Ok(new_stake)
+}
+
+/// Removes `amount` from `staker`'s balance and every aggregate derived from it.
+fn debit_stake(
+    storage: &mut dyn Storage,
+    env: &Env,
+    staker: &Addr,
+    amount: Uint128,
+) -> Result<Uint128, ContractError> {
+    let current_stake = STAKES.may_load(storage, staker)?.unwrap_or(Uint128::zero());
+    let new_stake = checked_sub("stakes", current_stake, amount)?;
+    if new_stake.is_zero() {
+        STAKES.remove(storage, staker);
+        STAKE_TRANCHES.remove(storage, staker);
+        index_staker(storage, staker, false)?;
+    } else {
+        STAKES.save(storage, staker, &new_stake)?;
+        // the newest stake leaves first, so older tranches keep their age
+        let mut tranches = STAKE_TRANCHES.may_load(storage, staker)?.unwrap_or_default();
+        let mut left = amount;
+        while let Some(newest) = tranches.last_mut() {
+            if newest.amount > left {
+                newest.amount -= left;
+                break;
+            }
+            left -= newest.amount;
+            tranches.pop();
+        }
+        STAKE_TRANCHES.save(storage, staker, &tranches)?;
+    }
+    update_distribution(storage, current_stake, new_stake)?;
+    TOTAL_STAKED.update(storage, |total| checked_sub("total_staked", total, amount))?;
+    LIFETIME_TOTALS.update(storage, |mut totals| -> Result<_, ContractError> {
+        totals.unstaked = checked_add("lifetime_totals", totals.unstaked, amount)?;
+        Ok(totals)
+    })?;
+    record_daily_activity(storage, env, staker, Uint128::zero(), amount)?;
+    Ok(new_stake)
+}
+
+/// Adds an outflow to the rolling TVL window, opening a new window once the current one has
+/// elapsed. Must run before the outflow is debited so a new window starts from the prior TVL.
+/// Returns the alert event the first time the window's outflow exceeds the configured share.
+fn track_outflow(
+    storage: &mut dyn Storage,
+    env: &Env,
+    config: &Config,
+    amount: Uint128,
+) -> Result<Option<Event>, ContractError> {
+    let Some(alert) = &config.tvl_alert else {
+        return Ok(None);
+    };
+    let mut window = match TVL_WINDOW.may_load(storage)? {
+        Some(window) if !window.ends.is_expired(&env.block) => window,
+        _ => TvlWindow {
+            ends: alert.window.after(&env.block),
+            start_tvl: TOTAL_STAKED.load(storage)?,
+            outflow: Uint128::zero(),
+            alerted: false,
+        },
+    };
+    window.outflow = checked_add("tvl_window", window.outflow, amount)?;
+
+    let threshold = window.start_tvl.mul_floor(alert.max_drop);
+    let event = if !window.alerted && window.outflow > threshold {
+        window.alerted = true;
+        if alert.auto_pause {
+            PAUSED.save(storage, &true)?;
+        }
+        Some(
+            Event::new("tvl_drop_alert")
+                .add_attribute("window_ends", window.ends.to_string())
+                .add_attribute("start_tvl", window.start_tvl.to_string())
+                .add_attribute("outflow", window.outflow.to_string())
+                .add_attribute("paused", alert.auto_pause.to_string()),
+        )
+    } else {
+        None
+    };
+    TVL_WINDOW.save(storage, &window)?;
+    Ok(event)
+}
+
+/// Whether withdrawals are going through recovery mode, which only opens while paused
+fn in_recovery(storage: &dyn Storage, config: &Config) -> StdResult<bool> {
+    Ok(config.recovery_mode && PAUSED.load(storage)?)
+}
+
+/// Fee on withdrawing `amount`, at the rate of the highest tier its share of the pool reaches.
+/// Recovery-mode withdrawals from a paused pool return the principal in full. Must run before
+/// the amount is debited.
+fn exit_fee(storage: &dyn Storage, config: &Config, amount: Uint128) -> Result<Uint128, ContractError> {
+    if config.exit_fee_tiers.is_empty() || amount.is_zero() {
+        return Ok(Uint128::zero());
+    }
+    if in_recovery(storage, config)? {
+        return Ok(Uint128::zero());
+    }
+    let total_staked = TOTAL_STAKED.load(storage)?;
+    if total_staked.is_zero() {
+        return Ok(Uint128::zero());
+    }
+    let share = Decimal::from_ratio(amount, total_staked);
+    let rate = config
+        .exit_fee_tiers
+        .iter()
+        .rev()
+        .find(|tier| share >= tier.min_share)
+        .map(|tier| tier.fee)
+        .unwrap_or_default();
+    Ok(amount.mul_floor(rate))
+}
+
+/// Adds a deposit to the staker's locked amount while a deposit lock is configured.
+fn record_deposit(
+    storage: &mut dyn Storage,
+    env: &Env,
+    config: &Config,
+    staker: &Addr,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    if config.deposit_lock_blocks == 0 {
+        return Ok(());
+    }
+    let locked = match RECENT_DEPOSITS.may_load(storage, staker)? {
+        Some(deposit)
+            if env.block.height < checked_add_blocks("recent_deposits", deposit.height, config.deposit_lock_blocks)? =>
+        {
+            deposit.amount
+        }
+        _ => Uint128::zero(),
+    };
+    let deposit = RecentDeposit {
+        height: env.block.height,
+        amount: checked_add("recent_deposits", locked, amount)?,
+    };
+    RECENT_DEPOSITS.save(storage, staker, &deposit)?;
+    Ok(())
+}
+
+/// Rejects withdrawals that would dip into deposits still inside the lock window.
+fn ensure_unlocked(
+    storage: &dyn Storage,
+    env: &Env,
+    config: &Config,
+    staker: &Addr,
+    amount: Uint128,
+    stake: Uint128,
+) -> Result<(), ContractError> {
+    if config.deposit_lock_blocks == 0 {
+        return Ok(());
+    }
+    if let Some(deposit) = RECENT_DEPOSITS.may_load(storage, staker)? {
+        let unlocks_at = checked_add_blocks("recent_deposits", deposit.height, config.deposit_lock_blocks)?;
+        if env.block.height < unlocks_at && amount > stake.saturating_sub(deposit.amount) {
+            return Err(ContractError::DepositLocked { unlocks_at });
+        }
+    }
+    Ok(())
+}
+
+/// First height a commit can be revealed at. A commit made before the rule was lifted still
+/// honours a delay of one block.
+fn revealable_at(config: &Config, commit: &UnstakeCommit) -> u64 {
+    commit.height.saturating_add(config.commit_reveal.as_ref().map_or(1, |rule| rule.reveal_delay))
+}
+
+/// Any interaction by the owner of an imported stake proves the key is alive and ends clawback eligibility.
+fn clear_clawback(storage: &mut dyn Storage, staker: &Addr) -> bool {
+    if CLAWBACKS.has(storage, staker) {
+        CLAWBACKS.remove(storage, staker);
+        return true;
+    }
+    false
+}
+
+/// Also records the call as the owner's heartbeat, which a guardian's claim is measured against.
+fn ensure_owner(storage: &mut dyn Storage, env: &Env, info: &MessageInfo) -> Result<(), ContractError> {
+    if info.sender != STATE.load(storage)?.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    OWNER_HEARTBEAT.save(
+        storage,
+        &Heartbeat {
+            height: env.block.height,
+            time: env.block.time,
+        },
+    )?;
+    Ok(())
+}
+
+fn claimable_at(guardian: &Guardian, heartbeat: &Heartbeat) -> Expiration {
+    match guardian.inactivity {
+        Duration::Height(blocks) => Expiration::AtHeight(heartbeat.height.saturating_add(blocks)),
+        Duration::Time(seconds) => Expiration::AtTime(heartbeat.time.plus_seconds(seconds)),
+    }
+}
+
+/// When the pool is restricted to non-contract accounts, rejects addresses the chain knows as contracts.
+fn ensure_not_contract(deps: Deps, config: &Config, staker: &Addr) -> Result<(), ContractError> {
+    if config.reject_contract_stakers
+        && deps.querier.query_wasm_contract_info(staker).is_ok()
+    {
+        return Err(ContractError::ContractStakerRejected {
+            address: staker.to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum RebateAction {
+    Stake,
+    Unstake,
+}
+
+/// Counts the action and credits its rebate while the rebate pool can cover it. Nothing is
+/// written unless a rebate program is configured and the action moved some principal.
+fn accrue_rebate(
+    storage: &mut dyn Storage,
+    config: &Config,
+    staker: &Addr,
+    action: RebateAction,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let Some(rates) = &config.rebate_rates else {
+        return Ok(());
+    };
+    if amount.is_zero() {
+        return Ok(());
+    }
+    let mut account = REBATE_ACCOUNTS.may_load(storage, staker)?.unwrap_or_default();
+    let rate = match action {
+        RebateAction::Stake => {
+            account.stakes += 1;
+            rates.stake
+        }
+        RebateAction::Unstake => {
+            account.unstakes += 1;
+            rates.unstake
+        }
+    };
+    let pool = REBATE_POOL.may_load(storage)?.unwrap_or_default();
+    if !rate.is_zero() && rate <= pool {
+        REBATE_POOL.save(storage, &(pool - rate))?;
+        account.accrued = checked_add("rebates", account.accrued, rate)?;
+    }
+    REBATE_ACCOUNTS.save(storage, staker, &account)?;
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Interaction {
+    Stake,
+    Unstake,
+    Claim,
+    Other,
+}
+
+/// Applies `change` to the queue counters `Health` reports; callers only count entries they
+/// actually added or removed.
+fn update_queue_depth(storage: &mut dyn Storage, change: impl FnOnce(&mut QueueDepth)) -> StdResult<()> {
+    let mut depth = QUEUE_DEPTH.may_load(storage)?.unwrap_or_default();
+    change(&mut depth);
+    QUEUE_DEPTH.save(storage, &depth)
+}
+
+/// Bumps the counter of `user`'s own action and its last interaction time.
+fn record_interaction(
+    storage: &mut dyn Storage,
+    env: &Env,
+    user: &Addr,
+    interaction: Interaction,
+) -> Result<(), ContractError> {
+    let now = env.block.time;
+    let mut stats = USER_STATS.may_load(storage, user)?.unwrap_or(UserStats {
+        stakes: 0,
+        unstakes: 0,
+        claims: 0,
+        first_interaction: now,
+        last_interaction: now,
+    });
+    match interaction {
+        Interaction::Stake => stats.stakes += 1,
+        Interaction::Unstake => stats.unstakes += 1,
+        Interaction::Claim => stats.claims += 1,
+        Interaction::Other => {}
+    }
+    stats.last_interaction = now;
+    USER_STATS.save(storage, user, &stats)?;
+    Ok(())
+}
+
+/// Unstake confirmation delay in force for `staker` at `height`; zero when none is required,
+/// including once a delay lowered to zero takes effect.
+fn confirmation_delay(storage: &dyn Storage, staker: &Addr, height: u64) -> StdResult<u64> {
+    Ok(UNSTAKE_CONFIRMATIONS
+        .may_load(storage, staker)?
+        .map_or(0, |confirmation| confirmation.blocks_at(height)))
+}
+
+/// Exits above the commit-reveal threshold must go through `CommitUnstake` and `RevealUnstake`.
+fn ensure_below_commit_threshold(config: &Config, amount: Uint128) -> Result<(), ContractError> {
+    match &config.commit_reveal {
+        Some(rule) if amount > rule.threshold => Err(ContractError::CommitRequired {
+            threshold: rule.threshold,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Withdrawals stay open through a pause in recovery mode, unless the funds were evacuated.
+fn ensure_can_withdraw(deps: Deps) -> Result<(), ContractError> {
+    // the config is only read when paused, keeping the common path cheap
+    if !PAUSED.load(deps.storage)? {
+        return Ok(());
+    }
+    if EVACUATION.exists(deps.storage) || !CONFIG.load(deps.storage)?.recovery_mode {
+        return Err(ContractError::Paused {});
+    }
+    ensure_not_rebuilding(deps)
+}
+
+/// A rebuild only runs while paused, but recovery-mode withdrawals and clawbacks would still
+/// change stakes under it.
+fn ensure_not_rebuilding(deps: Deps) -> Result<(), ContractError> {
+    match REBUILD.may_load(deps.storage)? {
+        Some(progress) => Err(ContractError::Rebuilding {
+            kind: progress.kind.as_str().to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Fails with a typed error when the pool can't cover a payout of `needed`, if the owner turned
+/// the check on.
+fn ensure_liquidity(deps: Deps, env: &Env, config: &Config, needed: Uint128) -> Result<(), ContractError> {
+    if !config.liquidity_check {
+        return Ok(());
+    }
+    let available = deps.querier.query_balance(&env.contract.address, STAKE_DENOM)?.amount;
+    if available < needed {
+        return Err(ContractError::InsufficientContractLiquidity {
+            denom: STAKE_DENOM.to_string(),
+            needed,
+            available,
+        });
+    }
+    Ok(())
+}
+
+fn ensure_deposits_open(env: &Env, config: &Config) -> Result<(), ContractError> {
+    match config.deposits_close_at {
+        Some(closed_at) if closed_at.is_expired(&env.block) => Err(ContractError::DepositsClosed { closed_at }),
+        _ => Ok(()),
+    }
+}
+
+fn ensure_not_paused(deps: Deps) -> Result<(), ContractError> {
+    if PAUSED.load(deps.storage)? {
+        return Err(ContractError::Paused {});
+    }
+    Ok(())
+}
+
+fn checked_add(ledger: &str, a: Uint128, b: Uint128) -> Result<Uint128, ContractError> {
+    a.checked_add(b).map_err(|_| ContractError::Overflow { ledger: ledger.to_string() })
+}
+
+fn checked_sub(ledger: &str, a: Uint128, b: Uint128) -> Result<Uint128, ContractError> {
+    a.checked_sub(b).map_err(|_| ContractError::Underflow { ledger: ledger.to_string() })
+}
+
+fn checked_add_blocks(ledger: &str, height: u64, blocks: u64) -> Result<u64, ContractError> {
+    height.checked_add(blocks).ok_or_else(|| ContractError::Overflow { ledger: ledger.to_string() })
+}
+
+/// Records `(debit, credit, amount)` transfers in the double-entry ledger, loading and saving it
+/// once however many there are.
+fn post(
+    storage: &mut dyn Storage,
+    transfers: &[(LedgerAccount, LedgerAccount, Uint128)],
+) -> Result<(), ContractError> {
+    let overflow = || ContractError::Overflow { ledger: "ledger".to_string() };
+    let mut ledger = LEDGER.may_load(storage)?.unwrap_or_default();
+    for (debit, credit, amount) in transfers {
+        if amount.is_zero() {
+            continue;
+        }
+        let amount = Int256::from(*amount);
+        let balance = ledger.balance_mut(*debit);
+        *balance = balance.checked_add(amount).map_err(|_| overflow())?;
+        let balance = ledger.balance_mut(*credit);
+        *balance = balance.checked_sub(amount).map_err(|_| overflow())?;
+    }
+    LEDGER.save(storage, &ledger)?;
+    Ok(())
+}
+
+/// Index of the power-of-ten bucket a balance falls into (1-9 => 0, 10-99 => 1, ...).
+fn distribution_bucket(amount: Uint128) -> u8 {
+    amount.u128().ilog10() as u8
+}
+
+/// Moves a staker between distribution buckets when their balance changes from `old` to `new`.
+pub(crate) fn update_distribution(
+    storage: &mut dyn Storage,
+    old: Uint128,
+    new: Uint128,
+) -> Result<(), ContractError> {
+    if !old.is_zero() {
+        let bucket = distribution_bucket(old);
+        let count = DISTRIBUTION
+            .may_load(storage, bucket)?
+            .unwrap_or_default()
+            .checked_sub(1)
+            .ok_or_else(|| ContractError::Underflow { ledger: "distribution".to_string() })?;
+        if count == 0 {
+            DISTRIBUTION.remove(storage, bucket);
+        } else {
+            DISTRIBUTION.save(storage, bucket, &count)?;
+        }
+    }
+    if !new.is_zero() {
+        DISTRIBUTION.update(storage, distribution_bucket(new), |count| -> StdResult<_> {
+            Ok(count.unwrap_or_default() + 1)
+        })?;
+    }
+    Ok(())
+}
+
+/// Adds a stake/unstake to the aggregates of the current day, counting each address once per day.
+fn record_daily_activity(
+    storage: &mut dyn Storage,
+    env: &Env,
+    addr: &Addr,
+    staked_in: Uint128,
+    unstaked_out: Uint128,
+) -> Result<(), ContractError> {
+    let day = env.block.time.seconds() / SECONDS_PER_DAY;
+    let mut stats = DAILY_STATS.may_load(storage, day)?.unwrap_or_default();
+    stats.staked_in = checked_add("daily_stats", stats.staked_in, staked_in)?;
+    stats.unstaked_out = checked_add("daily_stats", stats.unstaked_out, unstaked_out)?;
+    if !DAILY_ACTIVE.has(storage, (day, addr)) {
+        DAILY_ACTIVE.save(storage, (day, addr), &Empty {})?;
+        stats.active_addresses += 1;
+    }
+    DAILY_STATS.save(storage, day, &stats)?;
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidMigrationContract {
+            contract: stored.contract,
+        });
+    }
+
+    let from = parse_version(&stored.version)?;
+    let to = parse_version(CONTRACT_VERSION)?;
+    migrations::run(deps.storage, &env, &from, &to)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
+fn parse_version(version: &str) -> Result<semver::Version, ContractError> {
+    version.parse().map_err(|_| ContractError::InvalidVersion {
+        version: version.to_string(),
+    })
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Count {} => to_json_binary(&query::count(deps)?),
+        QueryMsg::Stake { address } => to_json_binary(&query::stake(deps, address)?),
+        QueryMsg::Stakes { addresses } => to_json_binary(&query::stakes(deps, addresses)?),
+        QueryMsg::ShareOfPool { address } => to_json_binary(&query::share_of_pool(deps, address)?),
+        QueryMsg::Config {} => to_json_binary(&CONFIG.load(deps.storage)?),
+        QueryMsg::AssetInfo {} => to_json_binary(&query::asset_info(deps)?),
+        QueryMsg::Distribution {} => to_json_binary(&query::distribution(deps)?),
+        QueryMsg::Metrics {} => to_json_binary(&query::metrics(deps)?),
+        QueryMsg::DailyStats {
+            start_day,
+            end_day,
+            limit,
+        } => to_json_binary(&query::daily_stats(deps, start_day, end_day, limit)?),
+        QueryMsg::FeeDiscount { address } => to_json_binary(&query::fee_discount(deps, address)?),
+        QueryMsg::HasMinStake {
+            address,
+            amount,
+            min_duration,
+        } => to_json_binary(&query::has_min_stake(deps, env, address, amount, min_duration)?),
+        QueryMsg::PendingImports { start_after, limit } => {
+            to_json_binary(&query::pending_imports(deps, start_after, limit)?)
+        }
+        QueryMsg::Violations { start_after, limit } => {
+            to_json_binary(&query::violations(deps, start_after, limit)?)
+        }
+        QueryMsg::Profiles { start_after, limit } => {
+            to_json_binary(&query::profiles(deps, start_after, limit)?)
+        }
+        QueryMsg::TrustedPools { start_after, limit } => {
+            to_json_binary(&query::trusted_pools(deps, start_after, limit)?)
+        }
+        QueryMsg::HashedStakes { start_after, limit } => {
+            to_json_binary(&query::hashed_stakes(deps, start_after, limit)?)
+        }
+        QueryMsg::Guardian {} => to_json_binary(&query::guardian(deps)?),
+        QueryMsg::Health {} => to_json_binary(&query::health(deps, env)?),
+        QueryMsg::TrialBalance {} => to_json_binary(&query::trial_balance(deps, env)?),
+        QueryMsg::UnstakeOrders { start_after, limit } => {
+            to_json_binary(&query::unstake_orders(deps, start_after, limit)?)
+        }
+        QueryMsg::Rebates { address } => to_json_binary(&query::rebates(deps, address)?),
+        QueryMsg::Portfolio { address } => to_json_binary(&query::portfolio(deps, env, address)?),
+        QueryMsg::Nonce { address } => to_json_binary(&query::nonce(deps, address)?),
+        QueryMsg::UserStats { address } => to_json_binary(&query::user_stats(deps, address)?),
+        QueryMsg::Aggregate { queries } => to_json_binary(&query::aggregate(deps, env, queries)?),
+    }
+}
+
+pub mod query {
+    use super::*;
+
+    pub fn count(deps: Deps) -> StdResult<GetCountResponse> {
+        let state = STATE.load(deps.storage)?;
+        Ok(GetCountResponse { count: state.count })
+    }
+
+    pub fn stake(deps: Deps, address: String) -> StdResult<GetStakeResponse> {
+        let addr = deps.api.addr_validate(&address)?;
+        let amount = STAKES.may_load(deps.storage, &addr)?.unwrap_or(Uint128::zero());
+        Ok(GetStakeResponse { amount })
+    }
+
+    pub fn stakes(deps: Deps, addresses: Vec<String>) -> StdResult<GetStakesResponse> {
+        if addresses.len() > MAX_LIMIT as usize {
+            return Err(cosmwasm_std::StdError::generic_err(format!(
+                "Cannot query more than {} addresses at once",
+                MAX_LIMIT
+            )));
+        }
+        let stakes = addresses
+            .into_iter()
+            .map(|address| {
+                let addr = deps.api.addr_validate(&address)?;
+                let amount = STAKES.may_load(deps.storage, &addr)?.unwrap_or_default();
+                Ok(StakeEntry { address, amount })
+            })
+            .collect::<StdResult<_>>()?;
+        Ok(GetStakesResponse { stakes })
+    }
+
+    pub fn share_of_pool(deps: Deps, address: String) -> StdResult<ShareOfPoolResponse> {
+        let addr = deps.api.addr_validate(&address)?;
+        let stake = STAKES.may_load(deps.storage, &addr)?.unwrap_or(Uint128::zero());
+        let total_staked = TOTAL_STAKED.load(deps.storage)?;
+        let share = if total_staked.is_zero() {
+            Decimal::zero()
+        } else {
+            Decimal::from_ratio(stake, total_staked)
+        };
+        Ok(ShareOfPoolResponse {
+            stake,
+            total_staked,
+            share,
+        })
+    }
+
+    pub fn asset_info(deps: Deps) -> StdResult<AssetInfoResponse> {
+        if let Some(metadata) = CONFIG.load(deps.storage)?.asset_metadata {
+            return Ok(AssetInfoResponse {
+                denom: STAKE_DENOM.to_string(),
+                symbol: Some(metadata.symbol),
+                decimals: Some(metadata.decimals),
+                logo_uri: metadata.logo_uri,
+            });
+        }
+
+        // chains without bank metadata for the denom just get the bare denom back
+        match deps.querier.query_denom_metadata(STAKE_DENOM) {
+            Ok(metadata) => Ok(AssetInfoResponse {
+                denom: STAKE_DENOM.to_string(),
+                decimals: metadata
+                    .denom_units
+                    .iter()
+                    .find(|unit| unit.denom == metadata.display)
+                    .map(|unit| unit.exponent),
+                symbol: Some(metadata.symbol).filter(|symbol| !symbol.is_empty()),
+                logo_uri: Some(metadata.uri).filter(|uri| !uri.is_empty()),
+            }),
+            Err(_) => Ok(AssetInfoResponse {
+                denom: STAKE_DENOM.to_string(),
+                symbol: None,
+                decimals: None,
+                logo_uri: None,
+            }),
+        }
+    }
+
+    pub fn distribution(deps: Deps) -> StdResult<DistributionResponse> {
+        let (records, malformed) = range_lenient(deps.storage, &DISTRIBUTION, None, None, usize::MAX);
+        let buckets = records
+            .into_iter()
+            .map(|(bucket, stakers)| DistributionBucket {
+                min_amount: Uint128::new(10u128.pow(bucket as u32)),
+                stakers,
+            })
+            .collect();
+        Ok(DistributionResponse { buckets, malformed })
+    }
+
+    pub fn metrics(deps: Deps) -> StdResult<MetricsResponse> {
+        let lifetime = LIFETIME_TOTALS.load(deps.storage)?;
+        Ok(MetricsResponse {
+            total_staked: TOTAL_STAKED.load(deps.storage)?,
+            lifetime_staked: lifetime.staked,
+            lifetime_unstaked: lifetime.unstaked,
+            lifetime_fees: lifetime.fees,
+        })
+    }
+
+    pub fn daily_stats(
+        deps: Deps,
+        start_day: Option<u64>,
+        end_day: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<DailyStatsResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let (records, malformed) = range_lenient(
+            deps.storage,
+            &DAILY_STATS,
+            start_day.map(Bound::inclusive),
+            end_day.map(Bound::inclusive),
+            limit,
+        );
+        let days = records
+            .into_iter()
+            .map(|(day, stats)| DailyStatsEntry {
+                day,
+                staked_in: stats.staked_in,
+                unstaked_out: stats.unstaked_out,
+                active_addresses: stats.active_addresses,
+            })
+            .collect();
+        Ok(DailyStatsResponse { days, malformed })
+    }
+
+    /// Discount of the highest tier whose threshold the address' stake reaches, zero below every tier.
+    pub fn fee_discount(deps: Deps, address: String) -> StdResult<FeeDiscountResponse> {
+        let addr = deps.api.addr_validate(&address)?;
+        let stake = STAKES.may_load(deps.storage, &addr)?.unwrap_or(Uint128::zero());
+        let discount = CONFIG
+            .load(deps.storage)?
+            .fee_discount_tiers
+            .iter()
+            .rev()
+            .find(|tier| stake >= tier.min_stake)
+            .map(|tier| tier.discount)
+            .unwrap_or_default();
+        Ok(FeeDiscountResponse { stake, discount })
+    }
+
+    /// The holding period restarts whenever the balance grows, so a qualifying answer means the
+    /// address held at least its current stake for the whole duration.
+    pub fn has_min_stake(
+        deps: Deps,
+        env: Env,
+        address: String,
+        amount: Uint128,
+        min_duration: Duration,
+    ) -> StdResult<HasMinStakeResponse> {
+        let addr = deps.api.addr_validate(&address)?;
+        let stake = STAKES.may_load(deps.storage, &addr)?.unwrap_or(Uint128::zero());
+        let tranches = STAKE_TRANCHES.may_load(deps.storage, &addr)?.unwrap_or_default();
+        let held = tranches
+            .iter()
+            .filter(|tranche| match min_duration {
+                Duration::Height(blocks) => env.block.height.saturating_sub(tranche.height) >= blocks,
+                Duration::Time(seconds) => env.block.time.seconds().saturating_sub(tranche.time.seconds()) >= seconds,
+            })
+            .fold(Uint128::zero(), |held, tranche| held.saturating_add(tranche.amount));
+        Ok(HasMinStakeResponse {
+            qualifies: !tranches.is_empty() && held >= amount,
+            stake,
+            staked_since: tranches.first().map(|tranche| tranche.time),
+        })
+    }
+
+    pub fn pending_imports(
+        deps: Deps,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<PendingImportsResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start_after = start_after
+            .map(|address| deps.api.addr_validate(&address))
+            .transpose()?;
+        let (records, malformed) =
+            range_lenient(deps.storage, &CLAWBACKS, start_after.as_ref().map(Bound::exclusive), None, limit);
+        let imports = records
+            .into_iter()
+            .map(|(address, clawback)| PendingImport {
+                address: address.to_string(),
+                amount: clawback.amount,
+                deadline: clawback.deadline,
+            })
+            .collect();
+        Ok(PendingImportsResponse { imports, malformed })
+    }
+
+    pub fn unstake_orders(
+        deps: Deps,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<UnstakeOrdersResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let (records, malformed) =
+            range_lenient(deps.storage, &UNSTAKE_ORDERS, start_after.map(Bound::exclusive), None, limit);
+        let orders = records
+            .into_iter()
+            .map(|(id, order)| UnstakeOrderEntry {
+                id,
+                staker: order.staker.to_string(),
+                amount: order.amount,
+                execute_at: order.execute_at,
+                bounty: order.bounty,
+                condition: order.condition,
+            })
+            .collect();
+        Ok(UnstakeOrdersResponse { orders, malformed })
+    }
+
+    pub fn rebates(deps: Deps, address: String) -> StdResult<RebatesResponse> {
+        let addr = deps.api.addr_validate(&address)?;
+        let account = REBATE_ACCOUNTS.may_load(deps.storage, &addr)?.unwrap_or_default();
+        Ok(RebatesResponse {
+            stakes: account.stakes,
+            unstakes: account.unstakes,
+            claimable: account.accrued,
+            pool: REBATE_POOL.may_load(deps.storage)?.unwrap_or_default(),
+        })
+    }
+
+    pub fn guardian(deps: Deps) -> StdResult<GuardianResponse> {
+        let guardian = GUARDIAN.may_load(deps.storage)?;
+        let last_owner_action = OWNER_HEARTBEAT.may_load(deps.storage)?;
+        let claimable_at = match (&guardian, &last_owner_action) {
+            (Some(guardian), Some(heartbeat)) => Some(claimable_at(guardian, heartbeat)),
+            _ => None,
+        };
+        Ok(GuardianResponse {
+            guardian,
+            last_owner_action,
+            claimable_at,
+        })
+    }
+
+    /// Only checks each scanned staker against `reject_contract_stakers`; pool-wide invariants are
+    /// left to `CheckInvariants`. Nothing is written, so it is safe to poll.
+    pub fn violations(
+        deps: Deps,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<ViolationsResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let config = CONFIG.load(deps.storage)?;
+        if config.hashed_enumeration {
+            let start_after = start_after.map(|hash| HexBinary::from_hex(&hash)).transpose()?;
+            let HashedScan {
+                stakers,
+                malformed,
+                last_scanned,
+            } = scan_staker_hashes(deps, start_after.as_ref().map(HexBinary::as_slice), limit)?;
+            let scanned: Vec<_> = stakers.into_iter().map(|(_, staker, stake)| (staker, stake)).collect();
+            return Ok(ViolationsResponse {
+                violations: contract_stakers(deps, &config, &scanned),
+                last_scanned: last_scanned.map(|hash| HexBinary::from(hash).to_hex()),
+                malformed,
+            });
+        }
+        let start_after = start_after
+            .map(|address| deps.api.addr_validate(&address))
+            .transpose()?;
+        let (scanned, malformed) =
+            range_lenient(deps.storage, &STAKES, start_after.as_ref().map(Bound::exclusive), None, limit);
+
+        let violations = contract_stakers(deps, &config, &scanned);
+        // a malformed record may be the last one scanned
+        let last_scanned = if scanned.len() + malformed.len() == limit {
+            let last_read = scanned.last().map(|(address, _)| address.as_bytes().to_vec());
+            let last_malformed = malformed.last().map(|record| record.key.to_vec());
+            last_read.max(last_malformed).map(|key| String::from_utf8_lossy(&key).into_owned())
+        } else {
+            None
+        };
+        Ok(ViolationsResponse {
+            violations,
+            last_scanned,
+            malformed,
+        })
+    }
+
+    fn contract_stakers(deps: Deps, config: &Config, scanned: &[(Addr, Uint128)]) -> Vec<Violation> {
+        scanned
+            .iter()
+            .filter(|(address, _)| {
+                config.reject_contract_stakers && deps.querier.query_wasm_contract_info(address).is_ok()
+            })
+            .map(|(address, stake)| Violation {
+                address: address.to_string(),
+                stake: *stake,
+                kind: ViolationKind::ContractStaker,
+            })
+            .collect()
+    }
+
+    pub fn nonce(deps: Deps, address: String) -> StdResult<NonceResponse> {
+        let addr = deps.api.addr_validate(&address)?;
+        Ok(NonceResponse {
+            nonce: NONCES.may_load(deps.storage, &addr)?.unwrap_or_default(),
+            signing_key: SIGNING_KEYS.may_load(deps.storage, &addr)?,
+        })
+    }
+
+    /// The pool holds a single denom, so every list has at most one entry per kind.
+    pub fn portfolio(deps: Deps, env: Env, address: String) -> StdResult<PortfolioResponse> {
+        let addr = deps.api.addr_validate(&address)?;
+        let config = CONFIG.load(deps.storage)?;
+        let stake = STAKES.may_load(deps.storage, &addr)?.unwrap_or_default();
+        let coin = |amount: Uint128| Coin::new(amount, STAKE_DENOM);
+
+        let mut positions = vec![];
+        let mut total_value = vec![];
+        if !stake.is_zero() {
+            positions.push(Position {
+                denom: STAKE_DENOM.to_string(),
+                amount: stake,
+                since: STAKE_TRANCHES
+                    .may_load(deps.storage, &addr)?
+                    .and_then(|tranches| tranches.first().map(|tranche| tranche.time)),
+            });
+            total_value.push(coin(stake));
+        }
+
+        let accrued = REBATE_ACCOUNTS.may_load(deps.storage, &addr)?.unwrap_or_default().accrued;
+        let pending_rewards = if accrued.is_zero() { vec![] } else { vec![coin(accrued)] };
+
+        let mut locks = vec![];
+        if config.deposit_lock_blocks > 0 {
+            if let Some(deposit) = RECENT_DEPOSITS.may_load(deps.storage, &addr)? {
+                let unlocks_at_height = deposit.height.saturating_add(config.deposit_lock_blocks);
+                if env.block.height < unlocks_at_height && !stake.is_zero() {
+                    locks.push(PortfolioLock::Deposit {
+                        denom: STAKE_DENOM.to_string(),
+                        amount: deposit.amount.min(stake),
+                        unlocks_at_height,
+                    });
+                }
+            }
+        }
+        if let Some(clawback) = CLAWBACKS.may_load(deps.storage, &addr)? {
+            locks.push(PortfolioLock::UnconfirmedImport {
+                denom: STAKE_DENOM.to_string(),
+                amount: clawback.amount,
+                deadline: clawback.deadline,
+            });
+        }
+
+        let mut pending_exits = vec![];
+        if let Some(commit) = UNSTAKE_COMMITS.may_load(deps.storage, &addr)? {
+            pending_exits.push(PendingExit::CommittedUnstake {
+                revealable_at_height: revealable_at(&config, &commit),
+            });
+        }
+        if let Some(pending) = PENDING_UNSTAKES.may_load(deps.storage, &addr)? {
+            pending_exits.push(PendingExit::UnconfirmedUnstake {
+                denom: STAKE_DENOM.to_string(),
+                amount: pending.amount,
+                confirmable_at_height: pending.confirmable_at,
+            });
+        }
+
+        Ok(PortfolioResponse {
+            positions,
+            total_value,
+            pending_rewards,
+            locks,
+            pending_exits,
+        })
+    }
+
+    pub fn user_stats(deps: Deps, address: String) -> StdResult<UserStatsResponse> {
+        let addr = deps.api.addr_validate(&address)?;
+        Ok(match USER_STATS.may_load(deps.storage, &addr)? {
+            Some(stats) => UserStatsResponse {
+                stakes: stats.stakes,
+                unstakes: stats.unstakes,
+                claims: stats.claims,
+                first_interaction: Some(stats.first_interaction),
+                last_interaction: Some(stats.last_interaction),
+            },
+            None => UserStatsResponse {
+                stakes: 0,
+                unstakes: 0,
+                claims: 0,
+                first_interaction: None,
+                last_interaction: None,
+            },
+        })
+    }
+
+    pub fn health(deps: Deps, env: Env) -> StdResult<HealthResponse> {
+        let total_staked = TOTAL_STAKED.load(deps.storage)?;
+        let balance = deps
+            .querier
+            .query_balance(&env.contract.address, STAKE_DENOM)?
+            .amount;
+        let depth = QUEUE_DEPTH.may_load(deps.storage)?.unwrap_or_default();
+        Ok(HealthResponse {
+            paused: PAUSED.load(deps.storage)?,
+            contract_version: get_contract_version(deps.storage)?.version,
+            invariants_ok: total_staked <= balance,
+            total_staked,
+            balance,
+            unstake_orders: depth.unstake_orders,
+            pending_unstakes: depth.pending_unstakes,
+        })
+    }
+
+    pub fn aggregate(
+        deps: Deps,
+        env: Env,
+        queries: Vec<QueryMsg>,
+    ) -> StdResult<AggregateResponse> {
+        if queries.len() > MAX_AGGREGATE_QUERIES {
+            return Err(cosmwasm_std::StdError::generic_err(format!(
+                "Cannot aggregate more than {} queries",
+                MAX_AGGREGATE_QUERIES
+            )));
+        }
+        let results = queries
+            .into_iter()
+            .map(|msg| match msg {
+                QueryMsg::Aggregate { .. } => Err(cosmwasm_std::StdError::generic_err(
+                    "Aggregate queries cannot be nested",
+                )),
+                msg => super::query(deps, env.clone(), msg),
+            })
+            .collect::<StdResult<_>>()?;
+        Ok(AggregateResponse { results })
+    }
+
+    pub fn profiles(
+        deps: Deps,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<ProfilesResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start_after = start_after
+            .map(|address| deps.api.addr_validate(&address))
+            .transpose()?;
+        let (records, malformed) =
+            range_lenient(deps.storage, &PROFILES, start_after.as_ref().map(Bound::exclusive), None, limit);
+        let profiles = records
+            .into_iter()
+            .map(|(address, profile)| ProfileEntry {
+                address: address.to_string(),
+                display_name: profile.display_name,
+                url: profile.url,
+                avatar_hash: profile.avatar_hash,
+            })
+            .collect();
+        Ok(ProfilesResponse { profiles, malformed })
+    }
+
+    pub fn trusted_pools(
+        deps: Deps,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<TrustedPoolsResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start_after = start_after
+            .map(|address| deps.api.addr_validate(&address))
+            .transpose()?;
+        let (records, malformed) =
+            range_lenient(deps.storage, &TRUSTED_POOLS, start_after.as_ref().map(Bound::exclusive), None, limit);
+        let pools = records
+            .into_iter()
+            .map(|(address, added_at_height)| TrustedPoolEntry {
+                address: address.to_string(),
+                added_at_height,
+            })
+            .collect();
+        Ok(TrustedPoolsResponse { pools, malformed })
+    }
+
+    pub fn hashed_stakes(
+        deps: Deps,
+        start_after: Option<HexBinary>,
+        limit: Option<u32>,
+    ) -> StdResult<HashedStakesResponse> {
+        if !CONFIG.load(deps.storage)?.hashed_enumeration {
+            return Err(cosmwasm_std::StdError::generic_err("hashed staker enumeration is not enabled"));
+        }
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let HashedScan {
+            stakers,
+            malformed,
+            last_scanned,
+        } = scan_staker_hashes(deps, start_after.as_ref().map(HexBinary::as_slice), limit)?;
+        let stakes = stakers
+            .into_iter()
+            .map(|(hash, _, amount)| HashedStakeEntry {
+                staker_hash: hash.into(),
+                amount,
+            })
+            .collect();
+        Ok(HashedStakesResponse {
+            stakes,
+            last_scanned: last_scanned.map(HexBinary::from),
+            malformed,
+        })
+    }
+
+    /// Postings always balance, so the ledger is reconciled against state it doesn't control:
+    /// the recorded stakes and the bank balance.
+    pub fn trial_balance(deps: Deps, env: Env) -> StdResult<TrialBalanceResponse> {
+        let ledger = LEDGER.may_load(deps.storage)?.unwrap_or_default();
+        let accounts: Vec<_> = LedgerAccount::ALL
+            .into_iter()
+            .map(|account| LedgerBalance {
+                account,
+                balance: ledger.balance(account),
+            })
+            .collect();
+        let total_staked = Int256::from(TOTAL_STAKED.load(deps.storage)?);
+        let balance = deps
+            .querier
+            .query_balance(&env.contract.address, STAKE_DENOM)?
+            .amount;
+        Ok(TrialBalanceResponse {
+            accounts,
+            principal_difference: Int256::zero().checked_sub(ledger.principal)?.checked_sub(total_staked)?,
+            cash_difference: ledger.cash.checked_sub(Int256::from(balance))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AssetMetadata;
+    use cosmwasm_std::testing::{
+        message_info, mock_dependencies, mock_dependencies_with_balance, mock_env,
+    };
+    use cosmwasm_std::{
+        attr, coins, from_json, ContractInfoResponse, ContractResult, DenomMetadata, DenomUnit, SystemError,
+        SystemResult, WasmQuery,
+    };
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 17, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(1000, "earth"));
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // it worked, let's query the state
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Count {}).unwrap();
+        let value: GetCountResponse = from_json(&res).unwrap();
+        assert_eq!(17, value.count);
+    }
+
+    #[test]
+    fn increment() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 17, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // beneficiary can release it
+        let info = message_info(&deps.api.addr_make("anyone"), &[]);
+        let msg = ExecuteMsg::Increment {};
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "increment"),
+                attr("sender", info.sender.as_str()),
+                attr("count", "18"),
+            ]
+        );
+
+        // should increase counter by 1
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Count {}).unwrap();
+        let value: GetCountResponse = from_json(&res).unwrap();
+        assert_eq!(18, value.count);
+    }
+
+    #[test]
+    fn reset() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 17, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // beneficiary can release it
+        let unauth_info = message_info(&deps.api.addr_make("anyone"), &[]);
+        let msg = ExecuteMsg::Reset { count: 5 };
+        let res = execute(deps.as_mut(), mock_env(), unauth_info, msg);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // only the original creator can reset the counter
+        let auth_info = message_info(&deps.api.addr_make("creator"), &[]);
+        let msg = ExecuteMsg::Reset { count: 5 };
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "reset"),
+                attr("sender", auth_info.sender.as_str()),
+                attr("count", "5"),
+            ]
+        );
+
+        // should now be 5
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Count {}).unwrap();
+        let value: GetCountResponse = from_json(&res).unwrap();
+        assert_eq!(5, value.count);
+    }
+
+    #[test]
+    fn stake_tokens() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(1000, "token"));
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(500, "token"));
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
+        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Stake { address: staker.sender.to_string() }).unwrap();
+        let value: GetStakeResponse = from_json(&res).unwrap();
+        assert_eq!(value.amount, Uint128::new(500));
+    }
+
+    #[test]
+    fn unstake_tokens() {
+        let mut deps = mock_dependencies();
+    
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(1000, "token"));
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    
+        let staker_addr = deps.api.addr_make("staker1");  // ← Creăm o adresă Bech32 validă
+        let staker = message_info(&staker_addr, &coins(500, "token"));
+    
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
+        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
+    
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(300), min_receive: None, recipients: None };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&staker_addr, &[]), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "unstake"),
+                attr("staker", staker_addr.as_str()),
+                attr("amount", "300"),
+                attr("denom", STAKE_DENOM),
+                attr("fee", "0"),
+                attr("new_balance", "200"),
+            ]
+        );
+    
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Stake { address: staker_addr.to_string() }).unwrap();
+        let value: GetStakeResponse = from_json(&res).unwrap();
+        assert_eq!(value.amount, Uint128::new(200));
+    }
+    
+    #[test]
+    fn stake_without_funds_should_fail() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(1000, "token"));
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        
+        let staker = message_info(&deps.api.addr_make("staker1"), &[]);
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
+        let err = execute(deps.as_mut(), mock_env(), staker, msg).unwrap_err();
+
+        assert!(format!("{:?}", err).contains("Insufficient funds sent for staking"));
+    }
+
+    #[test]
+    fn stake_in_another_denom_is_rejected() {
+        let mut deps = mock_dependencies_with_balance(&coins(100, "token"));
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(100))]),
+            asset_metadata: None,
+        };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(100, "token"));
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let mallory = deps.api.addr_make("mallory");
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
+        for funds in [coins(500, "junk"), vec![Coin::new(500u128, "junk"), Coin::new(500u128, "token")]] {
+            let err = execute(deps.as_mut(), mock_env(), message_info(&mallory, &funds), msg.clone()).unwrap_err();
+            assert_eq!(err, ContractError::InvalidDenom {});
+        }
+        assert_eq!(TOTAL_STAKED.load(&deps.storage).unwrap(), Uint128::new(100));
+
+        // nothing was credited, so the pool stays backed and open
+        let res = execute(deps.as_mut(), mock_env(), message_info(&mallory, &[]), ExecuteMsg::CheckInvariants {}).unwrap();
+        assert!(res.attributes.contains(&attr("status", "ok")));
+        assert!(!PAUSED.load(&deps.storage).unwrap());
+    }
+
+    #[test]
     fn unstake_more_than_staked_should_fail() {
         let mut deps = mock_dependencies();
-        
-        let msg = InstantiateMsg { count: 0 };
-        let info = mock_info("creator", &coins(1000, "token"));
-        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        
-        let staker = mock_info("staker1", &coins(500, "token"));
-        let stake_msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
-        execute(deps.as_mut(), mock_env(), staker.clone(), stake_msg).unwrap();
-        
-        let unstake_msg = ExecuteMsg::Unstake { amount: Uint128::new(1000) }; // Trying to unstake more than staked
-        let err = execute(deps.as_mut(), mock_env(), staker, unstake_msg).unwrap_err();
-        
-        assert_eq!(err, ContractError::Std(cosmwasm_std::StdError::generic_err("Cannot unstake more than your current balance")));
+        
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(1000, "token"));
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(500, "token"));
+        let stake_msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
+        execute(deps.as_mut(), mock_env(), staker.clone(), stake_msg).unwrap();
+        
+        let unstake_msg = ExecuteMsg::Unstake { amount: Uint128::new(1000), min_receive: None, recipients: None }; // Trying to unstake more than staked
+        let err = execute(deps.as_mut(), mock_env(), message_info(&staker.sender, &[]), unstake_msg).unwrap_err();
+        
+        assert_eq!(err, ContractError::Std(cosmwasm_std::StdError::generic_err("Cannot unstake more than your current balance")));
+    }
+
+    #[test]
+    fn zero_unstake_should_fail() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(1000, "token"));
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(500, "token"));
+        execute(deps.as_mut(), mock_env(), staker.clone(), ExecuteMsg::Stake { amount: Uint128::new(500) }).unwrap();
+
+        let recipients = Some(vec![(deps.api.addr_make("bob").to_string(), Decimal::one())]);
+        for recipients in [None, recipients] {
+            let msg = ExecuteMsg::Unstake { amount: Uint128::zero(), min_receive: None, recipients };
+            let err = execute(deps.as_mut(), mock_env(), message_info(&staker.sender, &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::Std(cosmwasm_std::StdError::generic_err("Unstake amount must be greater than zero")));
+        }
+        assert_eq!(LIFETIME_TOTALS.load(&deps.storage).unwrap().unstaked, Uint128::zero());
+    }
+
+    #[test]
+    fn unstake_full_balance_should_leave_zero() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(1000, "token"));
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        
+        let staker_addr = deps.api.addr_make("staker1");
+        let staker = message_info(&staker_addr, &coins(500, "token")); 
+    
+        let stake_msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
+        execute(deps.as_mut(), mock_env(), staker.clone(), stake_msg).unwrap();
+        
+        let unstake_msg = ExecuteMsg::Unstake { amount: Uint128::new(500), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&staker_addr, &[]), unstake_msg).unwrap();
+        
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Stake { address: staker_addr.as_str().to_string() }
+        ).unwrap();
+    
+        let value: GetStakeResponse = from_json(&res).unwrap();
+    
+        assert_eq!(value.amount, Uint128::zero());
+    }
+
+    #[test]
+    fn share_of_pool() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let staker1 = message_info(&deps.api.addr_make("staker1"), &coins(300, "token"));
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(300) };
+        execute(deps.as_mut(), mock_env(), staker1.clone(), msg).unwrap();
+
+        let staker2 = message_info(&deps.api.addr_make("staker2"), &coins(100, "token"));
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(100) };
+        execute(deps.as_mut(), mock_env(), staker2, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ShareOfPool { address: staker1.sender.to_string() }).unwrap();
+        let value: ShareOfPoolResponse = from_json(&res).unwrap();
+        assert_eq!(value.stake, Uint128::new(300));
+        assert_eq!(value.total_staked, Uint128::new(400));
+        assert_eq!(value.share, Decimal::percent(75));
+
+        // unstaking lowers both the stake and the pool total
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(200), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&staker1.sender, &[]), msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ShareOfPool { address: staker1.sender.to_string() }).unwrap();
+        let value: ShareOfPoolResponse = from_json(&res).unwrap();
+        assert_eq!(value.total_staked, Uint128::new(200));
+        assert_eq!(value.share, Decimal::percent(50));
+    }
+
+    #[test]
+    fn distribution() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        for (name, amount) in [("staker1", 5), ("staker2", 50), ("staker3", 75), ("staker4", 5000)] {
+            let staker = message_info(&deps.api.addr_make(name), &coins(amount, "token"));
+            let msg = ExecuteMsg::Stake { amount: Uint128::new(amount) };
+            execute(deps.as_mut(), mock_env(), staker, msg).unwrap();
+        }
+
+        // staker2 drops from the 10s bucket into the 1s bucket, staker4 exits entirely
+        let staker2 = message_info(&deps.api.addr_make("staker2"), &[]);
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(45), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), staker2, msg).unwrap();
+        let staker4 = message_info(&deps.api.addr_make("staker4"), &[]);
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(5000), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), staker4, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Distribution {}).unwrap();
+        let value: DistributionResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.buckets,
+            vec![
+                DistributionBucket { min_amount: Uint128::new(1), stakers: 2 },
+                DistributionBucket { min_amount: Uint128::new(10), stakers: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn daily_stats() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let mut env = mock_env();
+        let today = env.block.time.seconds() / SECONDS_PER_DAY;
+        let staker1 = message_info(&deps.api.addr_make("staker1"), &coins(300, "token"));
+        let staker2 = message_info(&deps.api.addr_make("staker2"), &coins(100, "token"));
+
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(300) };
+        execute(deps.as_mut(), env.clone(), staker1.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(100) };
+        execute(deps.as_mut(), env.clone(), staker2, msg).unwrap();
+        let staker1 = message_info(&staker1.sender, &[]);
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(50), min_receive: None, recipients: None };
+        execute(deps.as_mut(), env.clone(), staker1.clone(), msg).unwrap();
+
+        env.block.time = env.block.time.plus_days(1);
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(100), min_receive: None, recipients: None };
+        execute(deps.as_mut(), env.clone(), staker1, msg).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::DailyStats { start_day: None, end_day: None, limit: None }).unwrap();
+        let value: DailyStatsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.days,
+            vec![
+                DailyStatsEntry {
+                    day: today,
+                    staked_in: Uint128::new(400),
+                    unstaked_out: Uint128::new(50),
+                    active_addresses: 2,
+                },
+                DailyStatsEntry {
+                    day: today + 1,
+                    staked_in: Uint128::zero(),
+                    unstaked_out: Uint128::new(100),
+                    active_addresses: 1,
+                },
+            ]
+        );
+
+        // bounds are inclusive
+        let msg = QueryMsg::DailyStats { start_day: Some(today + 1), end_day: Some(today + 1), limit: None };
+        let res = query(deps.as_ref(), env, msg).unwrap();
+        let value: DailyStatsResponse = from_json(&res).unwrap();
+        assert_eq!(value.days.len(), 1);
+        assert_eq!(value.days[0].day, today + 1);
+    }
+
+    #[test]
+    fn stake_overflow_is_a_typed_error() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(u128::MAX, "token"));
+        let msg = ExecuteMsg::Stake { amount: Uint128::MAX };
+        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::Stake { amount: Uint128::MAX };
+        let err = execute(deps.as_mut(), mock_env(), staker, msg).unwrap_err();
+        assert_eq!(err, ContractError::Overflow { ledger: "stakes".to_string() });
+    }
+
+    #[test]
+    fn daily_stats_limit_is_capped() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let mut env = mock_env();
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(1, "token"));
+        for _ in 0..(MAX_LIMIT + 5) {
+            let msg = ExecuteMsg::Stake { amount: Uint128::new(1) };
+            execute(deps.as_mut(), env.clone(), staker.clone(), msg).unwrap();
+            env.block.time = env.block.time.plus_days(1);
+        }
+
+        let msg = QueryMsg::DailyStats { start_day: None, end_day: None, limit: None };
+        let value: DailyStatsResponse = from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        assert_eq!(value.days.len(), DEFAULT_LIMIT as usize);
+
+        let msg = QueryMsg::DailyStats { start_day: None, end_day: None, limit: Some(1000) };
+        let value: DailyStatsResponse = from_json(query(deps.as_ref(), env, msg).unwrap()).unwrap();
+        assert_eq!(value.days.len(), MAX_LIMIT as usize);
+    }
+
+    #[test]
+    fn migrate_from_v0_1_backfills_totals() {
+        let mut deps = mock_dependencies();
+
+        // v0.1 layout: only the counter state and per-address stakes
+        let owner = deps.api.addr_make("creator");
+        STATE.save(&mut deps.storage, &State { count: 0, owner }).unwrap();
+        STAKES.save(&mut deps.storage, &deps.api.addr_make("staker1"), &Uint128::new(300)).unwrap();
+        STAKES.save(&mut deps.storage, &deps.api.addr_make("staker2"), &Uint128::new(5)).unwrap();
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(res.attributes[1].value, "0.1.0");
+        let tranches = STAKE_TRANCHES.load(&deps.storage, &deps.api.addr_make("staker2")).unwrap();
+        let tranche = StakeTranche { amount: Uint128::new(5), height: mock_env().block.height, time: mock_env().block.time };
+        assert_eq!(tranches, vec![tranche]);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ShareOfPool { address: deps.api.addr_make("staker1").to_string() }).unwrap();
+        let value: ShareOfPoolResponse = from_json(&res).unwrap();
+        assert_eq!(value.total_staked, Uint128::new(305));
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Distribution {}).unwrap();
+        let value: DistributionResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.buckets,
+            vec![
+                DistributionBucket { min_amount: Uint128::new(1), stakers: 1 },
+                DistributionBucket { min_amount: Uint128::new(100), stakers: 1 },
+            ]
+        );
+        assert_eq!(get_contract_version(deps.as_ref().storage).unwrap().version, CONTRACT_VERSION);
+    }
+
+    #[test]
+    fn migrate_rejects_downgrade_and_foreign_contracts() {
+        let mut deps = mock_dependencies();
+
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "9.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::MigrationDowngrade { from: "9.0.0".to_string(), to: CONTRACT_VERSION.to_string() }
+        );
+
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.0.1").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert_eq!(err, ContractError::UnsupportedMigration { from: "0.0".to_string() });
+
+        set_contract_version(deps.as_mut().storage, "crates.io:other", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert_eq!(err, ContractError::InvalidMigrationContract { contract: "crates.io:other".to_string() });
+    }
+
+    #[test]
+    fn invariant_violation_pauses_pool() {
+        // the contract only holds 400 of the 500 tokens that will be recorded as staked
+        let mut deps = mock_dependencies_with_balance(&coins(400, "token"));
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(500, "token"));
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
+        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
+        let staker = message_info(&staker.sender, &[]);
+
+        let anyone = message_info(&deps.api.addr_make("anyone"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), anyone.clone(), ExecuteMsg::CheckInvariants {}).unwrap();
+        assert_eq!(res.events.len(), 1);
+        assert_eq!(res.events[0].ty, "invariant_violation");
+
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(100), min_receive: None, recipients: None };
+        let err = execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::Paused {});
+
+        // only the owner can resume the pool
+        let msg = ExecuteMsg::SetPaused { paused: false };
+        let err = execute(deps.as_mut(), mock_env(), anyone, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let msg = ExecuteMsg::SetPaused { paused: false };
+        execute(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(100), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), staker, msg).unwrap();
+    }
+
+    #[test]
+    fn staker_profiles() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(10, "token"));
+        let msg = ExecuteMsg::SetProfile {
+            display_name: Some("alice".to_string()),
+            url: None,
+            avatar_hash: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&staker.sender, &[]), msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::NotStaker {});
+
+        let stake = ExecuteMsg::Stake { amount: Uint128::new(10) };
+        execute(deps.as_mut(), mock_env(), staker.clone(), stake).unwrap();
+        let staker = message_info(&staker.sender, &[]);
+        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetProfile {
+            display_name: Some("a".repeat(MAX_DISPLAY_NAME_LEN + 1)),
+            url: None,
+            avatar_hash: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidProfile { .. }));
+
+        let msg = QueryMsg::Profiles { start_after: None, limit: None };
+        let value: ProfilesResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            value.profiles,
+            vec![ProfileEntry {
+                address: staker.sender.to_string(),
+                display_name: Some("alice".to_string()),
+                url: None,
+                avatar_hash: None,
+            }]
+        );
+
+        // clearing every field removes the profile
+        let msg = ExecuteMsg::SetProfile { display_name: None, url: None, avatar_hash: None };
+        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
+        let msg = QueryMsg::Profiles { start_after: None, limit: None };
+        let value: ProfilesResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(value.profiles.is_empty());
+    }
+
+    #[test]
+    fn fee_discount_tiers() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(500, "token"));
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
+        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
+        let staker = message_info(&staker.sender, &[]);
+
+        let fee_discount = |deps: Deps| -> FeeDiscountResponse {
+            let msg = QueryMsg::FeeDiscount { address: staker.sender.to_string() };
+            from_json(query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(fee_discount(deps.as_ref()).discount, Decimal::zero());
+
+        let unsorted = vec![
+            FeeDiscountTier { min_stake: Uint128::new(1000), discount: Decimal::percent(20) },
+            FeeDiscountTier { min_stake: Uint128::new(100), discount: Decimal::percent(10) },
+        ];
+        let msg = ExecuteMsg::SetFeeDiscountTiers { tiers: unsorted };
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFeeDiscountTiers { .. }));
+
+        let tiers = vec![
+            FeeDiscountTier { min_stake: Uint128::new(100), discount: Decimal::percent(10) },
+            FeeDiscountTier { min_stake: Uint128::new(1000), discount: Decimal::percent(20) },
+        ];
+        let msg = ExecuteMsg::SetFeeDiscountTiers { tiers: tiers.clone() };
+        let err = execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let msg = ExecuteMsg::SetFeeDiscountTiers { tiers };
+        execute(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        let value = fee_discount(deps.as_ref());
+        assert_eq!(value.stake, Uint128::new(500));
+        assert_eq!(value.discount, Decimal::percent(10));
+    }
+
+    #[test]
+    fn has_min_stake() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let mut env = mock_env();
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(500, "token"));
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
+        execute(deps.as_mut(), env.clone(), staker.clone(), msg).unwrap();
+
+        let has_min_stake_for = |deps: Deps, env: Env, amount: u128, min_duration: Duration| -> HasMinStakeResponse {
+            let msg = QueryMsg::HasMinStake {
+                address: staker.sender.to_string(),
+                amount: Uint128::new(amount),
+                min_duration,
+            };
+            from_json(query(deps, env, msg).unwrap()).unwrap()
+        };
+        let has_min_stake = |deps: Deps, env: Env, amount: u128| -> HasMinStakeResponse {
+            has_min_stake_for(deps, env, amount, Duration::Time(7 * SECONDS_PER_DAY))
+        };
+
+        env.block.time = env.block.time.plus_days(6);
+        assert!(!has_min_stake(deps.as_ref(), env.clone(), 500).qualifies);
+
+        // the holding period can also be measured in blocks
+        env.block.height += 100;
+        assert!(has_min_stake_for(deps.as_ref(), env.clone(), 500, Duration::Height(100)).qualifies);
+        assert!(!has_min_stake_for(deps.as_ref(), env.clone(), 500, Duration::Height(101)).qualifies);
+
+        env.block.time = env.block.time.plus_days(1);
+        let value = has_min_stake(deps.as_ref(), env.clone(), 500);
+        assert!(value.qualifies);
+        assert_eq!(value.staked_since, Some(mock_env().block.time));
+        assert!(!has_min_stake(deps.as_ref(), env.clone(), 501).qualifies);
+
+        // a partial unstake keeps the holding period, a top-up starts its own
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(100), min_receive: None, recipients: None };
+        execute(deps.as_mut(), env.clone(), message_info(&staker.sender, &[]), msg).unwrap();
+        let value = has_min_stake(deps.as_ref(), env.clone(), 400);
+        assert!(value.qualifies);
+        assert_eq!(value.staked_since, Some(mock_env().block.time));
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(100) };
+        execute(deps.as_mut(), env.clone(), staker.clone(), msg).unwrap();
+        assert!(has_min_stake(deps.as_ref(), env.clone(), 400).qualifies);
+        assert!(!has_min_stake(deps.as_ref(), env.clone(), 401).qualifies);
+
+        // unstakes take the newest stake first
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(150), min_receive: None, recipients: None };
+        execute(deps.as_mut(), env.clone(), message_info(&staker.sender, &[]), msg).unwrap();
+        assert!(has_min_stake(deps.as_ref(), env.clone(), 350).qualifies);
+        assert!(!has_min_stake(deps.as_ref(), env.clone(), 351).qualifies);
+    }
+
+    #[test]
+    fn top_ups_keep_the_age_of_older_stake() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let mut env = mock_env();
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(500, "token"));
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
+        execute(deps.as_mut(), env.clone(), staker.clone(), msg).unwrap();
+
+        env.block.height += 1;
+        env.block.time = env.block.time.plus_days(365);
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(1) };
+        execute(deps.as_mut(), env.clone(), message_info(&staker.sender, &coins(1, "token")), msg).unwrap();
+
+        let msg = QueryMsg::HasMinStake {
+            address: staker.sender.to_string(),
+            amount: Uint128::new(500),
+            min_duration: Duration::Time(SECONDS_PER_DAY),
+        };
+        let value: HasMinStakeResponse = from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        assert!(value.qualifies);
+        assert_eq!(value.stake, Uint128::new(501));
+        assert_eq!(value.staked_since, Some(mock_env().block.time));
+
+        // past the tranche cap, new stake joins the newest tranche rather than an older one
+        for _ in 0..2 * MAX_STAKE_TRANCHES {
+            env.block.height += 1;
+            let msg = ExecuteMsg::Stake { amount: Uint128::new(1) };
+            execute(deps.as_mut(), env.clone(), message_info(&staker.sender, &coins(1, "token")), msg).unwrap();
+        }
+        let tranches = STAKE_TRANCHES.load(&deps.storage, &staker.sender).unwrap();
+        assert_eq!(tranches.len(), MAX_STAKE_TRANCHES);
+        assert_eq!(tranches[0].amount, Uint128::new(500));
+    }
+
+    #[test]
+    fn lifetime_metrics() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(500, "token"));
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
+        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(500), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&staker.sender, &[]), msg).unwrap();
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(200) };
+        execute(deps.as_mut(), mock_env(), staker, msg).unwrap();
+
+        let value: MetricsResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Metrics {}).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            MetricsResponse {
+                total_staked: Uint128::new(200),
+                lifetime_staked: Uint128::new(700),
+                lifetime_unstaked: Uint128::new(500),
+                lifetime_fees: Uint128::zero(),
+            }
+        );
+    }
+
+    #[test]
+    fn batch_stake_for() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let recipients = vec![
+            (alice.to_string(), Uint128::new(300)),
+            (bob.to_string(), Uint128::new(200)),
+            (alice.to_string(), Uint128::new(100)),
+        ];
+
+        let treasury = message_info(&deps.api.addr_make("treasury"), &coins(550, "token"));
+        let msg = ExecuteMsg::BatchStakeFor { recipients: recipients.clone() };
+        let err = execute(deps.as_mut(), mock_env(), treasury, msg).unwrap_err();
+        assert_eq!(err, ContractError::FundsMismatch { expected: Uint128::new(600), received: Uint128::new(550) });
+
+        let treasury = message_info(&deps.api.addr_make("treasury"), &coins(600, "earth"));
+        let msg = ExecuteMsg::BatchStakeFor { recipients: recipients.clone() };
+        let err = execute(deps.as_mut(), mock_env(), treasury, msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidDenom {});
+
+        let treasury = message_info(&deps.api.addr_make("treasury"), &coins(600, "token"));
+        let msg = ExecuteMsg::BatchStakeFor { recipients };
+        execute(deps.as_mut(), mock_env(), treasury.clone(), msg).unwrap();
+
+        for (addr, expected) in [(&alice, 400), (&bob, 200), (&treasury.sender, 0)] {
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::Stake { address: addr.to_string() }).unwrap();
+            let value: GetStakeResponse = from_json(&res).unwrap();
+            assert_eq!(value.amount, Uint128::new(expected));
+        }
+        let value: MetricsResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Metrics {}).unwrap()).unwrap();
+        assert_eq!(value.total_staked, Uint128::new(600));
+    }
+
+    #[test]
+    fn instantiate_with_initial_stakes() {
+        let mut deps = mock_dependencies();
+
+        let team = deps.api.addr_make("team");
+        let investor = deps.api.addr_make("investor");
+        let initial_stakes = vec![(team.to_string(), Uint128::new(700)), (investor.to_string(), Uint128::new(300))];
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: Some(initial_stakes.clone()), asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(999, "token"));
+        let err = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::FundsMismatch { expected: Uint128::new(1000), received: Uint128::new(999) });
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: Some(initial_stakes), asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(1000, "token"));
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = QueryMsg::ShareOfPool { address: team.to_string() };
+        let value: ShareOfPoolResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(value.stake, Uint128::new(700));
+        assert_eq!(value.total_staked, Uint128::new(1000));
+    }
+
+    #[test]
+    fn imported_stakes_can_be_clawed_back_unless_confirmed() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let active = deps.api.addr_make("active");
+        let lost = deps.api.addr_make("lost");
+        let msg = ExecuteMsg::ImportStakes {
+            stakes: vec![(active.to_string(), Uint128::new(100)), (lost.to_string(), Uint128::new(50))],
+            // about 30 days of 6s blocks
+            confirm_within: Duration::Height(432_000),
+        };
+        let funded_owner = message_info(&owner.sender, &coins(150, "token"));
+        execute(deps.as_mut(), mock_env(), funded_owner, msg).unwrap();
+
+        let msg = QueryMsg::PendingImports { start_after: None, limit: None };
+        let value: PendingImportsResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(value.imports.len(), 2);
+
+        // the active key proves itself by interacting with the pool
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(10), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&active, &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::ClawbackImport { address: lost.to_string() };
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::ClawbackNotDue { .. }));
+
+        let mut env = mock_env();
+        env.block.height += 432_000 - 1;
+        let msg = ExecuteMsg::ClawbackImport { address: lost.to_string() };
+        let err = execute(deps.as_mut(), env.clone(), owner.clone(), msg).unwrap_err();
+        let deadline = Expiration::AtHeight(env.block.height + 1);
+        assert_eq!(err, ContractError::ClawbackNotDue { deadline });
+
+        env.block.height += 1;
+        let msg = ExecuteMsg::ClawbackImport { address: active.to_string() };
+        let err = execute(deps.as_mut(), env.clone(), owner.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::NoPendingImport {});
+
+        let msg = ExecuteMsg::ClawbackImport { address: lost.to_string() };
+        let res = execute(deps.as_mut(), env.clone(), owner.clone(), msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(cosmwasm_std::BankMsg::Send {
+                to_address: owner.sender.to_string(),
+                amount: coins(50, "token"),
+            })
+        );
+
+        let value: MetricsResponse = from_json(query(deps.as_ref(), env, QueryMsg::Metrics {}).unwrap()).unwrap();
+        assert_eq!(value.total_staked, Uint128::new(90));
+    }
+
+    #[test]
+    fn contract_stakers_can_be_rejected() {
+        let mut deps = mock_dependencies();
+        let vault = deps.api.addr_make("vault");
+        let vault_addr = vault.to_string();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::ContractInfo { contract_addr } if *contract_addr == vault_addr => {
+                let info = ContractInfoResponse::new(1, Addr::unchecked("creator"), None, false, None);
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::NoSuchContract { addr: "unknown".to_string() }),
+        });
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        // allowed by default
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(10) };
+        execute(deps.as_mut(), mock_env(), message_info(&vault, &coins(10, "token")), msg).unwrap();
+
+        let msg = ExecuteMsg::SetRejectContractStakers { reject: true };
+        execute(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(10) };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&vault, &coins(10, "token")), msg).unwrap_err();
+        assert_eq!(err, ContractError::ContractStakerRejected { address: vault.to_string() });
+
+        let msg = ExecuteMsg::BatchStakeFor { recipients: vec![(vault.to_string(), Uint128::new(10))] };
+        let funder = message_info(&deps.api.addr_make("funder"), &coins(10, "token"));
+        let err = execute(deps.as_mut(), mock_env(), funder, msg).unwrap_err();
+        assert_eq!(err, ContractError::ContractStakerRejected { address: vault.to_string() });
+
+        let human = message_info(&deps.api.addr_make("human"), &coins(10, "token"));
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(10) };
+        execute(deps.as_mut(), mock_env(), human, msg).unwrap();
+
+        // the vault staked before the rule was tightened and is flagged for the operator
+        let msg = QueryMsg::Violations { start_after: None, limit: None };
+        let value: ViolationsResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            ViolationsResponse {
+                violations: vec![Violation {
+                    address: vault.to_string(),
+                    stake: Uint128::new(10),
+                    kind: ViolationKind::ContractStaker,
+                }],
+                last_scanned: None,
+                malformed: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn stake_for_with_callback() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let router = deps.api.addr_make("router");
+        let user = deps.api.addr_make("user");
+        let msg = ExecuteMsg::StakeFor {
+            recipient: user.to_string(),
+            callback: Some(StakeCallback {
+                contract: router.to_string(),
+                msg: Some(Binary::from(b"zap-42")),
+            }),
+            receipt: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&router, &coins(250, "token")), msg).unwrap();
+
+        let expected = AfterStakeMsg {
+            staker: user.to_string(),
+            amount: Uint128::new(250),
+            new_balance: Uint128::new(250),
+            msg: Some(Binary::from(b"zap-42")),
+        };
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].msg, expected.into_cosmos_msg(router.as_str()).unwrap());
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Stake { address: user.to_string() }).unwrap();
+        let value: GetStakeResponse = from_json(&res).unwrap();
+        assert_eq!(value.amount, Uint128::new(250));
+
+        // without a callback nothing is sent back
+        let msg = ExecuteMsg::StakeFor { recipient: user.to_string(), callback: None, receipt: None };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&router, &coins(50, "token")), msg).unwrap();
+        assert!(res.messages.is_empty());
+    }
+
+    #[test]
+    fn asset_info_from_config_or_chain() {
+        let mut deps = mock_dependencies();
+        deps.querier.bank.set_denom_metadata(&[DenomMetadata {
+            base: "token".to_string(),
+            display: "TKN".to_string(),
+            symbol: "TKN".to_string(),
+            denom_units: vec![
+                DenomUnit { denom: "token".to_string(), exponent: 0, aliases: vec![] },
+                DenomUnit { denom: "TKN".to_string(), exponent: 6, aliases: vec![] },
+            ],
+            ..Default::default()
+        }]);
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let value: AssetInfoResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::AssetInfo {}).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            AssetInfoResponse {
+                denom: "token".to_string(),
+                symbol: Some("TKN".to_string()),
+                decimals: Some(6),
+                logo_uri: None,
+            }
+        );
+
+        // configured metadata wins over the chain's
+        let mut deps = mock_dependencies();
+        let asset_metadata = AssetMetadata {
+            symbol: "STK".to_string(),
+            decimals: 18,
+            logo_uri: Some("ipfs://logo".to_string()),
+        };
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: Some(asset_metadata) };
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let value: AssetInfoResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::AssetInfo {}).unwrap()).unwrap();
+        assert_eq!(value.symbol, Some("STK".to_string()));
+        assert_eq!(value.decimals, Some(18));
+        assert_eq!(value.logo_uri, Some("ipfs://logo".to_string()));
+    }
+
+    #[test]
+    fn get_stakes_batch() {
+        let mut deps = mock_dependencies();
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let carol = deps.api.addr_make("carol");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![
+                (alice.to_string(), Uint128::new(10)),
+                (bob.to_string(), Uint128::new(20)),
+            ]),
+            asset_metadata: None,
+        };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(30, "token"));
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let addresses = vec![bob.to_string(), carol.to_string(), alice.to_string()];
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Stakes { addresses }).unwrap();
+        let value: GetStakesResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.stakes,
+            vec![
+                StakeEntry { address: bob.to_string(), amount: Uint128::new(20) },
+                StakeEntry { address: carol.to_string(), amount: Uint128::zero() },
+                StakeEntry { address: alice.to_string(), amount: Uint128::new(10) },
+            ]
+        );
+
+        let addresses = vec![alice.to_string(); MAX_LIMIT as usize + 1];
+        query(deps.as_ref(), mock_env(), QueryMsg::Stakes { addresses }).unwrap_err();
+    }
+
+    #[test]
+    fn aggregate_query() {
+        let mut deps = mock_dependencies();
+
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 7,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(40))]),
+            asset_metadata: None,
+        };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(40, "token"));
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let queries = vec![
+            QueryMsg::Count {},
+            QueryMsg::Stake { address: alice.to_string() },
+            QueryMsg::Metrics {},
+        ];
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Aggregate { queries }).unwrap();
+        let value: AggregateResponse = from_json(&res).unwrap();
+        assert_eq!(value.results.len(), 3);
+        let count: GetCountResponse = from_json(&value.results[0]).unwrap();
+        assert_eq!(count.count, 7);
+        let stake: GetStakeResponse = from_json(&value.results[1]).unwrap();
+        assert_eq!(stake.amount, Uint128::new(40));
+        let metrics: MetricsResponse = from_json(&value.results[2]).unwrap();
+        assert_eq!(metrics.total_staked, Uint128::new(40));
+
+        // nesting and oversized batches are rejected
+        let queries = vec![QueryMsg::Aggregate { queries: vec![] }];
+        query(deps.as_ref(), mock_env(), QueryMsg::Aggregate { queries }).unwrap_err();
+        let queries = vec![QueryMsg::Count {}; MAX_AGGREGATE_QUERIES + 1];
+        query(deps.as_ref(), mock_env(), QueryMsg::Aggregate { queries }).unwrap_err();
+    }
+
+    #[test]
+    fn health_reports_invariant_status() {
+        let mut deps = mock_dependencies_with_balance(&coins(50, "token"));
+
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(50))]),
+            asset_metadata: None,
+        };
+        let info = message_info(&deps.api.addr_make("creator"), &coins(50, "token"));
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Health {}).unwrap();
+        let value: HealthResponse = from_json(&res).unwrap();
+        assert!(!value.paused);
+        assert!(value.invariants_ok);
+        assert_eq!(value.contract_version, CONTRACT_VERSION);
+        assert_eq!(value.total_staked, Uint128::new(50));
+
+        // the contract loses funds: health flags it but, unlike CheckInvariants, does not pause
+        deps.querier
+            .bank
+            .update_balance(mock_env().contract.address, coins(20, "token"));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Health {}).unwrap();
+        let value: HealthResponse = from_json(&res).unwrap();
+        assert!(!value.invariants_ok);
+        assert!(!value.paused);
+        assert_eq!(value.balance, Uint128::new(20));
+    }
+
+    #[test]
+    fn health_counts_the_unstake_backlog() {
+        let mut deps = mock_dependencies();
+        let vault = deps.api.addr_make("vault");
+        let vault_addr = vault.to_string();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::ContractInfo { contract_addr } if *contract_addr == vault_addr => {
+                let info = ContractInfoResponse::new(1, Addr::unchecked("creator"), None, false, None);
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::NoSuchContract { addr: "unknown".to_string() }),
+        });
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![
+                (vault.to_string(), Uint128::new(500)),
+                (alice.to_string(), Uint128::new(100)),
+            ]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(600, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner, msg).unwrap();
+        let vault_info = message_info(&vault, &[]);
+        let backlog = |deps: Deps| -> (u64, u64) {
+            let value: HealthResponse = from_json(query(deps, mock_env(), QueryMsg::Health {}).unwrap()).unwrap();
+            (value.unstake_orders, value.pending_unstakes)
+        };
+        assert_eq!(backlog(deps.as_ref()), (0, 0));
+
+        let mut later = mock_env();
+        later.block.height += 10;
+        let schedule = ExecuteMsg::ScheduleUnstake {
+            amount: Uint128::new(30),
+            execute_at: Expiration::AtHeight(later.block.height),
+            bounty: None,
+            condition: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), schedule.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), schedule).unwrap();
+        let msg = ExecuteMsg::CancelUnstakeOrder { id: 0 };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::SetUnstakeConfirmation { blocks: 10 };
+        execute(deps.as_mut(), mock_env(), vault_info.clone(), msg).unwrap();
+        let unstake = ExecuteMsg::Unstake { amount: Uint128::new(200), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), vault_info.clone(), unstake.clone()).unwrap();
+        assert_eq!(backlog(deps.as_ref()), (1, 1));
+
+        execute(deps.as_mut(), mock_env(), vault_info.clone(), ExecuteMsg::AbortUnstake {}).unwrap();
+        assert_eq!(backlog(deps.as_ref()), (1, 0));
+        execute(deps.as_mut(), mock_env(), vault_info.clone(), unstake).unwrap();
+        execute(deps.as_mut(), later.clone(), vault_info, ExecuteMsg::ConfirmUnstake {}).unwrap();
+        let keeper = message_info(&deps.api.addr_make("keeper"), &[]);
+        execute(deps.as_mut(), later, keeper, ExecuteMsg::ExecuteUnstakeOrder { id: 1 }).unwrap();
+        assert_eq!(backlog(deps.as_ref()), (0, 0));
+    }
+
+    #[test]
+    fn tvl_drop_alert() {
+        let mut deps = mock_dependencies();
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![
+                (alice.to_string(), Uint128::new(500)),
+                (bob.to_string(), Uint128::new(500)),
+            ]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(1000, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let owner = message_info(&owner.sender, &[]);
+
+        let window = Duration::Time(3600);
+        let alert = TvlAlert { window, max_drop: Decimal::percent(0), auto_pause: true };
+        let msg = ExecuteMsg::SetTvlAlert { alert: Some(alert) };
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTvlAlert { .. }));
+
+        let alert = TvlAlert { window, max_drop: Decimal::percent(20), auto_pause: true };
+        let msg = ExecuteMsg::SetTvlAlert { alert: Some(alert) };
+        execute(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        // 150 of 1000 leaves: below the 20% threshold
+        let alice_info = message_info(&alice, &[]);
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(150), min_receive: None, recipients: None };
+        let res = execute(deps.as_mut(), mock_env(), alice_info.clone(), msg).unwrap();
+        assert!(res.events.is_empty());
+
+        // once the window elapses the count starts over from the current 850
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(3600);
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(150), min_receive: None, recipients: None };
+        let res = execute(deps.as_mut(), env.clone(), alice_info, msg).unwrap();
+        assert!(res.events.is_empty());
+
+        // a further 100 brings the window's outflow to 250 of 850
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(100), min_receive: None, recipients: None };
+        let res = execute(deps.as_mut(), env.clone(), message_info(&bob, &[]), msg).unwrap();
+        assert_eq!(res.events.len(), 1);
+        assert_eq!(res.events[0].ty, "tvl_drop_alert");
+        assert!(res.events[0].attributes.iter().any(|a| a.key == "start_tvl" && a.value == "850"));
+        assert!(PAUSED.load(&deps.storage).unwrap());
+        assert_eq!(
+            TVL_WINDOW.load(&deps.storage).unwrap(),
+            TvlWindow {
+                ends: Expiration::AtTime(env.block.time.plus_seconds(3600)),
+                start_tvl: Uint128::new(850),
+                outflow: Uint128::new(250),
+                alerted: true,
+            }
+        );
+    }
+
+    #[test]
+    fn recovery_mode_allows_withdrawals_while_paused() {
+        let mut deps = mock_dependencies();
+
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(100))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(100, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let owner = message_info(&owner.sender, &[]);
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetPaused { paused: true }).unwrap();
+
+        let alice_info = message_info(&alice, &[]);
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(40), min_receive: None, recipients: None };
+        let err = execute(deps.as_mut(), mock_env(), alice_info.clone(), msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Paused {});
+
+        let recovery = ExecuteMsg::SetRecoveryMode { enabled: true };
+        let err = execute(deps.as_mut(), mock_env(), alice_info.clone(), recovery.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(deps.as_mut(), mock_env(), owner, recovery).unwrap();
+
+        let res = execute(deps.as_mut(), mock_env(), alice_info.clone(), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(STAKES.load(&deps.storage, &alice).unwrap(), Uint128::new(60));
+
+        // deposits stay blocked
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(10) };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&alice, &coins(10, "token")), msg)
+            .unwrap_err();
+        assert_eq!(err, ContractError::Paused {});
+    }
+
+    #[test]
+    fn scheduled_unstake() {
+        let mut deps = mock_dependencies();
+
+        let alice = deps.api.addr_make("alice");
+        let keeper = message_info(&deps.api.addr_make("keeper"), &[]);
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(100))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(100, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        let now = mock_env().block.time;
+        let alice_info = message_info(&alice, &[]);
+        for execute_at in [Expiration::AtTime(now), Expiration::Never {}] {
+            let msg = ExecuteMsg::ScheduleUnstake {
+                amount: Uint128::new(60),
+                execute_at,
+                bounty: None,
+                condition: None,
+            };
+            let err = execute(deps.as_mut(), mock_env(), alice_info.clone(), msg).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidOrder { .. }));
+        }
+
+        let due = now.plus_seconds(3600);
+        let execute_at = Expiration::AtTime(due);
+        let msg = ExecuteMsg::ScheduleUnstake {
+            amount: Uint128::new(60),
+            execute_at,
+            bounty: Some(Uint128::new(2)),
+            condition: None,
+        };
+        execute(deps.as_mut(), mock_env(), alice_info.clone(), msg).unwrap();
+
+        let msg = QueryMsg::UnstakeOrders { start_after: None, limit: None };
+        let value: UnstakeOrdersResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            value.orders,
+            vec![UnstakeOrderEntry {
+                id: 0,
+                staker: alice.to_string(),
+                amount: Uint128::new(60),
+                execute_at,
+                bounty: Uint128::new(2),
+                condition: None,
+            }]
+        );
+
+        let msg = ExecuteMsg::ExecuteUnstakeOrder { id: 0 };
+        let err = execute(deps.as_mut(), mock_env(), keeper.clone(), msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::OrderNotDue { execute_at });
+
+        // only the staker can cancel
+        let cancel = ExecuteMsg::CancelUnstakeOrder { id: 0 };
+        let err = execute(deps.as_mut(), mock_env(), keeper.clone(), cancel).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let mut env = mock_env();
+        env.block.time = due;
+        let res = execute(deps.as_mut(), env.clone(), keeper.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            res.messages.iter().map(|m| m.msg.clone()).collect::<Vec<_>>(),
+            vec![
+                cosmwasm_std::CosmosMsg::Bank(cosmwasm_std::BankMsg::Send {
+                    to_address: alice.to_string(),
+                    amount: coins(58, "token"),
+                }),
+                cosmwasm_std::CosmosMsg::Bank(cosmwasm_std::BankMsg::Send {
+                    to_address: keeper.sender.to_string(),
+                    amount: coins(2, "token"),
+                }),
+            ]
+        );
+        assert_eq!(STAKES.load(&deps.storage, &alice).unwrap(), Uint128::new(40));
+        let err = execute(deps.as_mut(), env, keeper, msg).unwrap_err();
+        assert_eq!(err, ContractError::OrderNotFound { id: 0 });
+    }
+
+    #[test]
+    fn conditional_unstake() {
+        let mut deps = mock_dependencies();
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let keeper = message_info(&deps.api.addr_make("keeper"), &[]);
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![
+                (alice.to_string(), Uint128::new(40)),
+                (bob.to_string(), Uint128::new(60)),
+            ]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(100, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        // alice exits if she ends up holding more than half of the pool
+        let now = mock_env().block.time;
+        let msg = ExecuteMsg::ScheduleUnstake {
+            amount: Uint128::new(40),
+            execute_at: Expiration::AtTime(now),
+            bounty: None,
+            condition: Some(OrderCondition::ShareAbove { share: Decimal::percent(50) }),
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+        // bob exits if the pool shrinks below 50
+        let msg = ExecuteMsg::ScheduleUnstake {
+            amount: Uint128::new(60),
+            execute_at: Expiration::AtTime(now),
+            bounty: None,
+            condition: Some(OrderCondition::TotalStakedBelow { amount: Uint128::new(50) }),
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&bob, &[]), msg).unwrap();
+
+        let trigger_alice = ExecuteMsg::ExecuteUnstakeOrder { id: 0 };
+        let err = execute(deps.as_mut(), mock_env(), keeper.clone(), trigger_alice.clone()).unwrap_err();
+        assert_eq!(err, ContractError::OrderConditionNotMet { id: 0 });
+        let trigger_bob = ExecuteMsg::ExecuteUnstakeOrder { id: 1 };
+        let err = execute(deps.as_mut(), mock_env(), keeper.clone(), trigger_bob.clone()).unwrap_err();
+        assert_eq!(err, ContractError::OrderConditionNotMet { id: 1 });
+
+        // bob leaves most of the way, so alice now holds 40 of 50
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(50), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&bob, &[]), msg).unwrap();
+        execute(deps.as_mut(), mock_env(), keeper.clone(), trigger_alice).unwrap();
+        assert!(!STAKES.has(&deps.storage, &alice));
+
+        // bob's order now exceeds his balance, so he cancels it
+        let err = execute(deps.as_mut(), mock_env(), keeper, trigger_bob).unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)));
+        let cancel = ExecuteMsg::CancelUnstakeOrder { id: 1 };
+        execute(deps.as_mut(), mock_env(), message_info(&bob, &[]), cancel).unwrap();
+        assert!(UNSTAKE_ORDERS.is_empty(&deps.storage));
+    }
+
+    #[test]
+    fn executed_orders_earn_the_unstake_rebate() {
+        let mut deps = mock_dependencies();
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(100))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(100, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let owner = message_info(&owner.sender, &[]);
+        let rates = RebateRates { stake: Uint128::zero(), unstake: Uint128::new(2) };
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetRebateRates { rates: Some(rates) }).unwrap();
+        let owner_funded = message_info(&owner.sender, &coins(10, "token"));
+        execute(deps.as_mut(), mock_env(), owner_funded, ExecuteMsg::FundRebates {}).unwrap();
+
+        let mut later = mock_env();
+        later.block.height += 1;
+        let msg = ExecuteMsg::ScheduleUnstake {
+            amount: Uint128::new(30),
+            execute_at: Expiration::AtHeight(later.block.height),
+            bounty: Some(Uint128::new(1)),
+            condition: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+        let keeper = message_info(&deps.api.addr_make("keeper"), &[]);
+        execute(deps.as_mut(), later, keeper, ExecuteMsg::ExecuteUnstakeOrder { id: 0 }).unwrap();
+
+        // the same bookkeeping as a direct unstake of the amount
+        let account = REBATE_ACCOUNTS.load(&deps.storage, &alice).unwrap();
+        assert_eq!((account.unstakes, account.accrued), (1, Uint128::new(2)));
+        assert_eq!(LIFETIME_TOTALS.load(&deps.storage).unwrap().unstaked, Uint128::new(30));
+    }
+
+    #[test]
+    fn zero_unstakes_do_not_drain_rebates() {
+        let mut deps = mock_dependencies();
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let rates = RebateRates { stake: Uint128::zero(), unstake: Uint128::new(2) };
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetRebateRates { rates: Some(rates) }).unwrap();
+        let owner_funded = message_info(&owner.sender, &coins(10, "token"));
+        execute(deps.as_mut(), mock_env(), owner_funded, ExecuteMsg::FundRebates {}).unwrap();
+
+        // a non-staker looping empty unstakes to a recipient
+        let mallory = deps.api.addr_make("mallory");
+        let recipients = Some(vec![(deps.api.addr_make("bob").to_string(), Decimal::one())]);
+        for _ in 0..5 {
+            let msg = ExecuteMsg::Unstake { amount: Uint128::zero(), min_receive: None, recipients: recipients.clone() };
+            execute(deps.as_mut(), mock_env(), message_info(&mallory, &[]), msg).unwrap_err();
+        }
+        assert_eq!(REBATE_POOL.load(&deps.storage).unwrap(), Uint128::new(10));
+        assert!(!REBATE_ACCOUNTS.has(&deps.storage, &mallory));
+    }
+
+    #[test]
+    fn gas_rebates() {
+        let mut deps = mock_dependencies();
+
+        let alice = deps.api.addr_make("alice");
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        // actions before the program starts are neither counted nor rebated
+        let stake = ExecuteMsg::Stake { amount: Uint128::new(100) };
+        let alice_funded = message_info(&alice, &coins(100, "token"));
+        execute(deps.as_mut(), mock_env(), alice_funded.clone(), stake.clone()).unwrap();
+        assert!(!REBATE_ACCOUNTS.has(&deps.storage, &alice));
+
+        let rates = RebateRates { stake: Uint128::new(3), unstake: Uint128::new(2) };
+        let msg = ExecuteMsg::SetRebateRates { rates: Some(rates) };
+        execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let fund = ExecuteMsg::FundRebates {};
+        let err = execute(deps.as_mut(), mock_env(), alice_funded.clone(), fund.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let owner_funded = message_info(&owner.sender, &coins(4, "token"));
+        execute(deps.as_mut(), mock_env(), owner_funded, fund).unwrap();
+
+        execute(deps.as_mut(), mock_env(), alice_funded, stake).unwrap();
+        // the pool has only 1 left, so this unstake is counted but not rebated
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(10), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+
+        let msg = QueryMsg::Rebates { address: alice.to_string() };
+        let value: RebatesResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            RebatesResponse {
+                stakes: 1,
+                unstakes: 1,
+                claimable: Uint128::new(3),
+                pool: Uint128::new(1),
+            }
+        );
+
+        let res = execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), ExecuteMsg::ClaimRebates { recipients: None })
+            .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cosmwasm_std::CosmosMsg::Bank(cosmwasm_std::BankMsg::Send {
+                to_address: alice.to_string(),
+                amount: coins(3, "token"),
+            })
+        );
+        let err = execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), ExecuteMsg::ClaimRebates { recipients: None })
+            .unwrap_err();
+        assert_eq!(err, ContractError::NoRebates {});
+        assert_eq!(USER_STATS.load(&deps.storage, &alice).unwrap().claims, 1);
+    }
+
+    #[cfg(feature = "testnet")]
+    #[test]
+    fn faucet_pays_surplus_with_cooldown() {
+        let mut deps = mock_dependencies_with_balance(&coins(1_500, "token"));
+
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(1_000))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(1_000, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        // only 500 of the balance is surplus over staked principal
+        let tester = message_info(&deps.api.addr_make("tester"), &[]);
+        let err = execute(deps.as_mut(), mock_env(), tester.clone(), ExecuteMsg::Faucet {}).unwrap_err();
+        assert_eq!(err, ContractError::FaucetEmpty {});
+
+        deps.querier
+            .bank
+            .update_balance(mock_env().contract.address, coins(2_500, "token"));
+        let res = execute(deps.as_mut(), mock_env(), tester.clone(), ExecuteMsg::Faucet {}).unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        let err = execute(deps.as_mut(), mock_env(), tester.clone(), ExecuteMsg::Faucet {}).unwrap_err();
+        let available_at = FAUCET_COOLDOWN.after(&mock_env().block);
+        assert_eq!(err, ContractError::FaucetCooldown { available_at });
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_days(1);
+        execute(deps.as_mut(), env, tester, ExecuteMsg::Faucet {}).unwrap();
+    }
+
+    #[cfg(feature = "testnet")]
+    #[test]
+    fn faucet_reserves_accrued_rebates() {
+        let mut deps = mock_dependencies();
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let rates = RebateRates { stake: Uint128::new(40), unstake: Uint128::zero() };
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetRebateRates { rates: Some(rates) }).unwrap();
+        let owner_funded = message_info(&owner.sender, &coins(100, "token"));
+        execute(deps.as_mut(), mock_env(), owner_funded, ExecuteMsg::FundRebates {}).unwrap();
+
+        // 40 of the 100 moves from the pool to alice's account, all of it is still owed
+        let alice = message_info(&deps.api.addr_make("alice"), &coins(10, "token"));
+        execute(deps.as_mut(), mock_env(), alice, ExecuteMsg::Stake { amount: Uint128::new(10) }).unwrap();
+
+        let tester = message_info(&deps.api.addr_make("tester"), &[]);
+        deps.querier
+            .bank
+            .update_balance(mock_env().contract.address, coins(10 + 100 + FAUCET_AMOUNT - 1, "token"));
+        let err = execute(deps.as_mut(), mock_env(), tester.clone(), ExecuteMsg::Faucet {}).unwrap_err();
+        assert_eq!(err, ContractError::FaucetEmpty {});
+        deps.querier
+            .bank
+            .update_balance(mock_env().contract.address, coins(10 + 100 + FAUCET_AMOUNT, "token"));
+        execute(deps.as_mut(), mock_env(), tester, ExecuteMsg::Faucet {}).unwrap();
+    }
+
+    #[test]
+    fn pagination_over_large_state() {
+        let mut deps = mock_dependencies();
+        let built = crate::fixtures::Fixture::new(11)
+            .stakers(120)
+            .history_days(45)
+            .unstake_orders(65)
+            .profiles(37)
+            .pending_imports(41)
+            .build(deps.as_mut());
+
+        let mut imports = vec![];
+        let mut start_after = None;
+        loop {
+            let msg = QueryMsg::PendingImports { start_after, limit: Some(MAX_LIMIT + 5) };
+            let page: PendingImportsResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            assert!(page.imports.len() <= MAX_LIMIT as usize);
+            match page.imports.last() {
+                Some(last) => start_after = Some(last.address.clone()),
+                None => break,
+            }
+            imports.extend(page.imports.into_iter().map(|import| import.address));
+        }
+        let mut expected: Vec<_> = built.imported.iter().map(Addr::to_string).collect();
+        expected.sort();
+        assert_eq!(imports, expected);
+
+        let mut profiles = 0;
+        let mut start_after = None;
+        loop {
+            let msg = QueryMsg::Profiles { start_after, limit: None };
+            let page: ProfilesResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            match page.profiles.last() {
+                Some(last) => start_after = Some(last.address.clone()),
+                None => break,
+            }
+            profiles += page.profiles.len();
+        }
+        assert_eq!(profiles, 37);
+
+        let mut orders = vec![];
+        let mut start_after = None;
+        loop {
+            let msg = QueryMsg::UnstakeOrders { start_after, limit: Some(7) };
+            let page: UnstakeOrdersResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            match page.orders.last() {
+                Some(last) => start_after = Some(last.id),
+                None => break,
+            }
+            orders.extend(page.orders.into_iter().map(|order| order.id));
+        }
+        assert_eq!(orders, (0..65).collect::<Vec<_>>());
+
+        // 45 days of history fill a whole page
+        let msg = QueryMsg::DailyStats { start_day: None, end_day: None, limit: Some(MAX_LIMIT) };
+        let page: DailyStatsResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(page.days.len(), MAX_LIMIT as usize);
+        assert_eq!(STATE.load(&deps.storage).unwrap().owner, built.owner);
+        assert_eq!(built.stakers.len(), 120);
+    }
+
+    #[test]
+    fn migrate_from_v0_1_rebuilds_aggregates_of_a_large_pool() {
+        let mut deps = mock_dependencies();
+        crate::fixtures::Fixture::new(3).stakers(500).build(deps.as_mut());
+        let distribution = query::distribution(deps.as_ref()).unwrap();
+        let total_staked = TOTAL_STAKED.load(&deps.storage).unwrap();
+
+        // strip back to the v0.1 layout
+        TOTAL_STAKED.remove(&mut deps.storage);
+        DISTRIBUTION.clear(&mut deps.storage);
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(TOTAL_STAKED.load(&deps.storage).unwrap(), total_staked);
+        assert_eq!(query::distribution(deps.as_ref()).unwrap(), distribution);
+    }
+
+    #[test]
+    fn legacy_message_names_still_deserialize() {
+        let alice = mock_dependencies().api.addr_make("alice");
+
+        let legacy: ExecuteMsg = from_json(br#"{"Stake":{"amount":"5"}}"#).unwrap();
+        assert_eq!(legacy, ExecuteMsg::Stake { amount: Uint128::new(5) });
+        let legacy: ExecuteMsg = from_json(br#"{"Increment":{}}"#).unwrap();
+        assert_eq!(legacy, ExecuteMsg::Increment {});
+        let legacy: ExecuteMsg = from_json(br#"{"SetPaused":{"paused":true}}"#).unwrap();
+        assert_eq!(legacy, ExecuteMsg::SetPaused { paused: true });
+        // new payloads are snake_case
+        let current = to_json_binary(&ExecuteMsg::SetPaused { paused: true }).unwrap();
+        assert_eq!(current.as_slice(), br#"{"set_paused":{"paused":true}}"#);
+
+        let json = format!(r#"{{"get_stake":{{"address":"{}"}}}}"#, alice);
+        let legacy: QueryMsg = from_json(json.as_bytes()).unwrap();
+        assert_eq!(legacy, QueryMsg::Stake { address: alice.to_string() });
+        let legacy: QueryMsg = from_json(br#"{"get_count":{}}"#).unwrap();
+        assert_eq!(legacy, QueryMsg::Count {});
+        let json = format!(r#"{{"get_stakes":{{"addresses":["{}"]}}}}"#, alice);
+        let legacy: QueryMsg = from_json(json.as_bytes()).unwrap();
+        assert_eq!(legacy, QueryMsg::Stakes { addresses: vec![alice.to_string()] });
+        let current = to_json_binary(&QueryMsg::Count {}).unwrap();
+        assert_eq!(current.as_slice(), br#"{"count":{}}"#);
+    }
+
+    #[test]
+    fn non_deposit_messages_reject_funds() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        let staker = message_info(&deps.api.addr_make("staker1"), &coins(100, "token"));
+        let msg = ExecuteMsg::Stake { amount: Uint128::new(100) };
+        execute(deps.as_mut(), mock_env(), staker.clone(), msg).unwrap();
+
+        // coins sent along with an unstake would be stuck in the pool
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(40), min_receive: None, recipients: None };
+        let err = execute(deps.as_mut(), mock_env(), staker.clone(), msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::UnexpectedFunds {});
+        let err = execute(deps.as_mut(), mock_env(), staker.clone(), ExecuteMsg::Increment {}).unwrap_err();
+        assert_eq!(err, ContractError::UnexpectedFunds {});
+
+        execute(deps.as_mut(), mock_env(), message_info(&staker.sender, &[]), msg).unwrap();
+        assert_eq!(STAKES.load(&deps.storage, &staker.sender).unwrap(), Uint128::new(60));
+    }
+
+    #[test]
+    fn user_stats() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        let alice = deps.api.addr_make("alice");
+        let user_stats = |deps: Deps| -> UserStatsResponse {
+            let msg = QueryMsg::UserStats { address: alice.to_string() };
+            from_json(query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(
+            user_stats(deps.as_ref()),
+            UserStatsResponse {
+                stakes: 0,
+                unstakes: 0,
+                claims: 0,
+                first_interaction: None,
+                last_interaction: None,
+            }
+        );
+
+        let start = mock_env().block.time;
+        let mut env = mock_env();
+        let stake = ExecuteMsg::Stake { amount: Uint128::new(100) };
+        execute(deps.as_mut(), env.clone(), message_info(&alice, &coins(100, "token")), stake.clone()).unwrap();
+        env.block.time = start.plus_days(1);
+        execute(deps.as_mut(), env.clone(), message_info(&alice, &coins(100, "token")), stake).unwrap();
+        env.block.time = start.plus_days(2);
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(50), min_receive: None, recipients: None };
+        execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), msg).unwrap();
+
+        // other own actions only move the last interaction time
+        env.block.time = start.plus_days(3);
+        let msg = ExecuteMsg::SetProfile { display_name: Some("alice".to_string()), url: None, avatar_hash: None };
+        execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), msg).unwrap();
+
+        assert_eq!(
+            user_stats(deps.as_ref()),
+            UserStatsResponse {
+                stakes: 2,
+                unstakes: 1,
+                claims: 0,
+                first_interaction: Some(start),
+                last_interaction: Some(start.plus_days(3)),
+            }
+        );
     }
 
     #[test]
-    fn unstake_full_balance_should_leave_zero() {
+    fn deposits_close_at_deadline() {
         let mut deps = mock_dependencies();
-        
-        let msg = InstantiateMsg { count: 0 };
-        let info = mock_info("creator", &coins(1000, "token"));
-        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        
-        let staker_addr = deps.api.addr_make("staker1");
-        let staker = mock_info(staker_addr.as_str(), &coins(500, "token")); 
-    
-        let stake_msg = ExecuteMsg::Stake { amount: Uint128::new(500) };
-        execute(deps.as_mut(), mock_env(), staker.clone(), stake_msg).unwrap();
-        
-        let unstake_msg = ExecuteMsg::Unstake { amount: Uint128::new(500) };
-        execute(deps.as_mut(), mock_env(), staker.clone(), unstake_msg).unwrap();
-        
-        let res = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::GetStake { address: staker_addr.as_str().to_string() }
-        ).unwrap();
-    
-        let value: GetStakeResponse = from_json(&res).unwrap();
-    
-        assert_eq!(value.amount, Uint128::zero());
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let start = mock_env().block.time;
+        let close_at = Expiration::AtTime(start.plus_days(1));
+        let msg = ExecuteMsg::SetDepositsCloseAt { close_at };
+        execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let alice = deps.api.addr_make("alice");
+        let stake = ExecuteMsg::Stake { amount: Uint128::new(100) };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &coins(100, "token")), stake.clone()).unwrap();
+
+        // a deadline can only move later, in the same unit
+        for close_at in [Expiration::AtTime(start.plus_hours(12)), Expiration::AtHeight(u64::MAX)] {
+            let msg = ExecuteMsg::SetDepositsCloseAt { close_at };
+            let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidDepositsCloseAt { .. }));
+        }
+        let close_at = Expiration::AtTime(start.plus_days(2));
+        let msg = ExecuteMsg::SetDepositsCloseAt { close_at };
+        execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let mut env = mock_env();
+        env.block.time = start.plus_days(2);
+        let err = execute(deps.as_mut(), env.clone(), message_info(&alice, &coins(100, "token")), stake).unwrap_err();
+        assert_eq!(err, ContractError::DepositsClosed { closed_at: close_at });
+        let msg = ExecuteMsg::SetDepositsCloseAt { close_at: Expiration::AtTime(start.plus_days(3)) };
+        let err = execute(deps.as_mut(), env.clone(), owner, msg).unwrap_err();
+        assert_eq!(err, ContractError::DepositsClosed { closed_at: close_at });
+
+        // existing stakes can still exit
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(100), min_receive: None, recipients: None };
+        execute(deps.as_mut(), env, message_info(&alice, &[]), msg).unwrap();
     }
-    
 
+    #[test]
+    fn progressive_exit_fee() {
+        let mut deps = mock_dependencies();
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![
+                (alice.to_string(), Uint128::new(9_000)),
+                (bob.to_string(), Uint128::new(1_000)),
+            ]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(10_000, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let owner = message_info(&owner.sender, &[]);
+
+        let unsorted = vec![
+            ExitFeeTier { min_share: Decimal::percent(5), fee: Decimal::percent(2) },
+            ExitFeeTier { min_share: Decimal::percent(1), fee: Decimal::percent(1) },
+        ];
+        let confiscatory = vec![ExitFeeTier { min_share: Decimal::percent(1), fee: Decimal::one() }];
+        for tiers in [unsorted, confiscatory] {
+            let msg = ExecuteMsg::SetExitFeeTiers { tiers };
+            let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidExitFeeTiers { .. }));
+        }
+
+        let tiers = vec![
+            ExitFeeTier { min_share: Decimal::percent(1), fee: Decimal::percent(1) },
+            ExitFeeTier { min_share: Decimal::percent(5), fee: Decimal::percent(2) },
+        ];
+        let msg = ExecuteMsg::SetExitFeeTiers { tiers };
+        execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let payouts = |res: &Response| -> Vec<(String, Uint128)> {
+            res.messages
+                .iter()
+                .map(|msg| match &msg.msg {
+                    cosmwasm_std::CosmosMsg::Bank(cosmwasm_std::BankMsg::Send { to_address, amount }) => {
+                        (to_address.clone(), amount[0].amount)
+                    }
+                    other => panic!("unexpected message {other:?}"),
+                })
+                .collect()
+        };
+
+        // 50 of 10_000 is below the first tier
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(50), min_receive: None, recipients: None };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&bob, &[]), msg).unwrap();
+        assert_eq!(payouts(&res), vec![(bob.to_string(), Uint128::new(50))]);
+
+        // 1_000 of 9_950 pays the 2% tier
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(1_000), min_receive: None, recipients: None };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+        assert_eq!(
+            payouts(&res),
+            vec![
+                (alice.to_string(), Uint128::new(980)),
+                (owner.sender.to_string(), Uint128::new(20)),
+            ]
+        );
+        assert_eq!(STAKES.load(&deps.storage, &alice).unwrap(), Uint128::new(8_000));
+        let value: MetricsResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Metrics {}).unwrap()).unwrap();
+        assert_eq!(value.lifetime_fees, Uint128::new(20));
+    }
+
+    #[test]
+    fn large_unstakes_need_commit_and_reveal() {
+        let mut deps = mock_dependencies();
+
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(1_000))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(1_000, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let owner = message_info(&owner.sender, &[]);
+
+        for reveal_delay in [0, u64::MAX] {
+            let rule = CommitReveal { threshold: Uint128::new(100), reveal_delay };
+            let msg = ExecuteMsg::SetCommitReveal { rule: Some(rule) };
+            let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidCommitReveal { .. }));
+        }
+        let rule = CommitReveal { threshold: Uint128::new(100), reveal_delay: 3 };
+        let msg = ExecuteMsg::SetCommitReveal { rule: Some(rule) };
+        execute(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        let alice_info = message_info(&alice, &[]);
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(100), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), alice_info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(500), min_receive: None, recipients: None };
+        let err = execute(deps.as_mut(), mock_env(), alice_info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::CommitRequired { threshold: Uint128::new(100) });
+
+        let reveal = ExecuteMsg::RevealUnstake { amount: Uint128::new(500), salt: "pepper".to_string(), min_receive: None };
+        let err = execute(deps.as_mut(), mock_env(), alice_info.clone(), reveal.clone()).unwrap_err();
+        assert_eq!(err, ContractError::NoUnstakeCommit {});
+
+        let hash = unstake_commitment(Uint128::new(500), "pepper");
+        let msg = ExecuteMsg::CommitUnstake { hash };
+        let res = execute(deps.as_mut(), mock_env(), alice_info.clone(), msg).unwrap();
+        assert!(res.attributes.iter().all(|attr| attr.value != "500"));
+
+        let mut env = mock_env();
+        env.block.height += 2;
+        let err = execute(deps.as_mut(), env.clone(), alice_info.clone(), reveal.clone()).unwrap_err();
+        assert_eq!(err, ContractError::RevealTooEarly { height: mock_env().block.height + 3 });
+
+        env.block.height += 1;
+        let wrong = ExecuteMsg::RevealUnstake { amount: Uint128::new(600), salt: "pepper".to_string(), min_receive: None };
+        let err = execute(deps.as_mut(), env.clone(), alice_info.clone(), wrong).unwrap_err();
+        assert_eq!(err, ContractError::InvalidReveal {});
+
+        let res = execute(deps.as_mut(), env.clone(), alice_info.clone(), reveal.clone()).unwrap();
+        assert_eq!(res.attributes[0], attr("action", "reveal_unstake"));
+        assert_eq!(STAKES.load(&deps.storage, &alice).unwrap(), Uint128::new(400));
+
+        // a commit is spent by its reveal
+        let err = execute(deps.as_mut(), env, alice_info, reveal).unwrap_err();
+        assert_eq!(err, ContractError::NoUnstakeCommit {});
+    }
+
+    #[test]
+    fn deposits_are_locked_for_configured_blocks() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        // without a lock, a deposit can leave in its own block
+        let alice = deps.api.addr_make("alice");
+        let stake = ExecuteMsg::Stake { amount: Uint128::new(100) };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &coins(100, "token")), stake.clone()).unwrap();
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(10), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+
+        // a lock that would outlast the chain is refused
+        let msg = ExecuteMsg::SetDepositLockBlocks { blocks: u64::MAX };
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDepositLock { .. }));
+        let update = ConfigUpdate { deposit_lock_blocks: Some(u64::MAX), ..Default::default() };
+        let msg = ExecuteMsg::UpdateConfig(Box::new(update));
+        execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+
+        let msg = ExecuteMsg::SetDepositLockBlocks { blocks: 2 };
+        execute(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        let mut env = mock_env();
+        env.block.height += 1;
+        execute(deps.as_mut(), env.clone(), message_info(&alice, &coins(100, "token")), stake).unwrap();
+
+        // the 90 staked before the lock can still leave
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(91), min_receive: None, recipients: None };
+        let err = execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::DepositLocked { unlocks_at: env.block.height + 2 });
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(90), min_receive: None, recipients: None };
+        execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), msg).unwrap();
+
+        env.block.height += 1;
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(100), min_receive: None, recipients: None };
+        let err = execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::DepositLocked { .. }));
+        env.block.height += 1;
+        execute(deps.as_mut(), env, message_info(&alice, &[]), msg).unwrap();
+        assert!(!STAKES.has(&deps.storage, &alice));
+    }
+
+    #[test]
+    fn update_config_validates_and_reports_a_diff() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let update = ConfigUpdate {
+            reject_contract_stakers: Some(true),
+            deposit_lock_blocks: Some(3),
+            ..ConfigUpdate::default()
+        };
+        let msg = ExecuteMsg::UpdateConfig(Box::new(update.clone()));
+        let anyone = message_info(&deps.api.addr_make("anyone"), &[]);
+        let err = execute(deps.as_mut(), mock_env(), anyone, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // an invalid field rejects the whole update
+        let invalid = ConfigUpdate {
+            commit_reveal: Some(CommitReveal { threshold: Uint128::new(10), reveal_delay: 0 }),
+            ..update.clone()
+        };
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::UpdateConfig(Box::new(invalid))).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCommitReveal { .. }));
+        assert_eq!(CONFIG.load(&deps.storage).unwrap(), Config::default());
+
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::UpdateConfig(Box::new(update.clone()))).unwrap();
+        assert!(res.attributes.contains(&attr("changed", "2")));
+        assert_eq!(
+            res.events,
+            vec![Event::new("config_update")
+                .add_attribute("reject_contract_stakers_old", "false")
+                .add_attribute("reject_contract_stakers_new", "true")
+                .add_attribute("deposit_lock_blocks_old", "0")
+                .add_attribute("deposit_lock_blocks_new", "3")]
+        );
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert!(config.reject_contract_stakers);
+        assert_eq!(config.deposit_lock_blocks, 3);
+
+        // re-applying the same values changes nothing
+        let res = execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::UpdateConfig(Box::new(update))).unwrap();
+        assert!(res.attributes.contains(&attr("changed", "0")));
+    }
+
+    #[test]
+    fn rebuild_repairs_drifted_aggregates_in_batches() {
+        let mut deps = mock_dependencies();
+        let built = crate::fixtures::Fixture::new(5).stakers(250).build(deps.as_mut());
+        let owner = message_info(&built.owner, &[]);
+        let distribution = query::distribution(deps.as_ref()).unwrap();
+        let total_staked = TOTAL_STAKED.load(&deps.storage).unwrap();
+
+        // a bug left both aggregates off
+        TOTAL_STAKED.save(&mut deps.storage, &Uint128::new(1)).unwrap();
+        DISTRIBUTION.save(&mut deps.storage, 0, &1_000).unwrap();
+
+        let rebuild = |kind| ExecuteMsg::Rebuild { kind, limit: Some(100) };
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), rebuild(RebuildKind::TotalStaked)).unwrap_err();
+        assert_eq!(err, ContractError::NotPaused {});
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetPaused { paused: true }).unwrap();
+        let alice = message_info(&built.stakers[0], &[]);
+        let err = execute(deps.as_mut(), mock_env(), alice, rebuild(RebuildKind::TotalStaked)).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), rebuild(RebuildKind::TotalStaked)).unwrap();
+        assert!(res.attributes.contains(&attr("done", "false")));
+        assert_eq!(TOTAL_STAKED.load(&deps.storage).unwrap(), Uint128::new(1));
+
+        // stakes stay frozen mid-rebuild, even for recovery-mode withdrawals, and one kind runs at a time
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetRecoveryMode { enabled: true }).unwrap();
+        let staker = message_info(&built.stakers[0], &[]);
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(1), min_receive: None, recipients: None };
+        let err = execute(deps.as_mut(), mock_env(), staker, msg).unwrap_err();
+        assert_eq!(err, ContractError::Rebuilding { kind: "total_staked".to_string() });
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), rebuild(RebuildKind::Distribution)).unwrap_err();
+        assert_eq!(err, ContractError::Rebuilding { kind: "total_staked".to_string() });
+
+        execute(deps.as_mut(), mock_env(), owner.clone(), rebuild(RebuildKind::TotalStaked)).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), rebuild(RebuildKind::TotalStaked)).unwrap();
+        assert!(res.attributes.contains(&attr("done", "true")));
+        assert!(res.attributes.contains(&attr("stakers", "250")));
+        assert!(res.attributes.contains(&attr("previous", "1")));
+        assert_eq!(TOTAL_STAKED.load(&deps.storage).unwrap(), total_staked);
+
+        // a single large batch, repeated, gives the same result
+        for _ in 0..2 {
+            let msg = ExecuteMsg::Rebuild { kind: RebuildKind::Distribution, limit: Some(MAX_REBUILD_LIMIT) };
+            let res = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+            assert!(res.attributes.contains(&attr("done", "true")));
+            assert_eq!(query::distribution(deps.as_ref()).unwrap(), distribution);
+        }
+    }
+
+    #[test]
+    fn unpausing_abandons_a_rebuild() {
+        let mut deps = mock_dependencies();
+        let built = crate::fixtures::Fixture::new(6).stakers(20).build(deps.as_mut());
+        let owner = message_info(&built.owner, &[]);
+        TOTAL_STAKED.save(&mut deps.storage, &Uint128::new(1)).unwrap();
+
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetPaused { paused: true }).unwrap();
+        let msg = ExecuteMsg::Rebuild { kind: RebuildKind::TotalStaked, limit: Some(5) };
+        execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetPaused { paused: false }).unwrap();
+        assert!(!REBUILD.exists(&deps.storage));
+        assert_eq!(TOTAL_STAKED.load(&deps.storage).unwrap(), Uint128::new(1));
+
+        // the next rebuild starts over with the other kind
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetPaused { paused: true }).unwrap();
+        let msg = ExecuteMsg::Rebuild { kind: RebuildKind::Distribution, limit: None };
+        let res = execute(deps.as_mut(), mock_env(), owner, msg).unwrap();
+        assert!(res.attributes.contains(&attr("stakers", "20")));
+    }
+
+    #[test]
+    fn portfolio_consolidates_positions_rewards_locks_and_exits() {
+        let mut deps = mock_dependencies();
+
+        let alice = deps.api.addr_make("alice");
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let portfolio = |deps: Deps| -> PortfolioResponse {
+            let msg = QueryMsg::Portfolio { address: alice.to_string() };
+            from_json(query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(
+            portfolio(deps.as_ref()),
+            PortfolioResponse {
+                positions: vec![],
+                total_value: vec![],
+                pending_rewards: vec![],
+                locks: vec![],
+                pending_exits: vec![],
+            }
+        );
+
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetDepositLockBlocks { blocks: 5 }).unwrap();
+        let rates = RebateRates { stake: Uint128::new(2), unstake: Uint128::zero() };
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetRebateRates { rates: Some(rates) }).unwrap();
+        let owner_funded = message_info(&owner.sender, &coins(10, "token"));
+        execute(deps.as_mut(), mock_env(), owner_funded, ExecuteMsg::FundRebates {}).unwrap();
+
+        let stake = ExecuteMsg::Stake { amount: Uint128::new(40) };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &coins(40, "token")), stake).unwrap();
+        let hash = unstake_commitment(Uint128::new(15), "salt");
+        let msg = ExecuteMsg::CommitUnstake { hash };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+
+        let height = mock_env().block.height;
+        assert_eq!(
+            portfolio(deps.as_ref()),
+            PortfolioResponse {
+                positions: vec![Position {
+                    denom: "token".to_string(),
+                    amount: Uint128::new(40),
+                    since: Some(mock_env().block.time),
+                }],
+                total_value: coins(40, "token"),
+                pending_rewards: coins(2, "token"),
+                locks: vec![PortfolioLock::Deposit {
+                    denom: "token".to_string(),
+                    amount: Uint128::new(40),
+                    unlocks_at_height: height + 5,
+                }],
+                pending_exits: vec![PendingExit::CommittedUnstake { revealable_at_height: height + 1 }],
+            }
+        );
+
+        // the lock lapses on its own
+        let mut later = mock_env();
+        later.block.height += 5;
+        let msg = QueryMsg::Portfolio { address: alice.to_string() };
+        let value: PortfolioResponse = from_json(query(deps.as_ref(), later, msg).unwrap()).unwrap();
+        assert!(value.locks.is_empty());
+    }
+
+    #[test]
+    fn pool_metadata_is_listed_in_config() {
+        let mut deps = mock_dependencies();
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let metadata = PoolMetadata {
+            name: "Community pool".to_string(),
+            description: Some("Stakes for the community".to_string()),
+            website: Some("https://example.com".to_string()),
+            logo_uri: None,
+        };
+        let msg = ExecuteMsg::SetPoolMetadata { metadata: Some(metadata.clone()) };
+        let alice = message_info(&deps.api.addr_make("alice"), &[]);
+        let err = execute(deps.as_mut(), mock_env(), alice, msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let config: Config = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.pool_metadata, Some(metadata.clone()));
+        let legacy: QueryMsg = from_json(br#"{"get_config":{}}"#).unwrap();
+        assert_eq!(legacy, QueryMsg::Config {});
+
+        let invalid = PoolMetadata { name: String::new(), ..metadata.clone() };
+        let msg = ExecuteMsg::SetPoolMetadata { metadata: Some(invalid) };
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidPoolMetadata { reason: "name is empty".to_string() });
+        let invalid = PoolMetadata { description: Some("x".repeat(MAX_POOL_DESCRIPTION_LEN + 1)), ..metadata };
+        let update = ConfigUpdate { pool_metadata: Some(invalid), ..Default::default() };
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::UpdateConfig(Box::new(update))).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidPoolMetadata { reason: "description exceeds 512 bytes".to_string() }
+        );
+
+        execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::SetPoolMetadata { metadata: None }).unwrap();
+        assert_eq!(CONFIG.load(&deps.storage).unwrap().pool_metadata, None);
+    }
+
+    #[test]
+    fn min_receive_guards_against_fee_changes() {
+        let mut deps = mock_dependencies();
+
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(1_000))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(1_000, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let owner = message_info(&owner.sender, &[]);
+
+        // alice signs expecting no fee, then the owner introduces one
+        let unstake = ExecuteMsg::Unstake { amount: Uint128::new(100), min_receive: Some(Uint128::new(100)), recipients: None };
+        let tiers = vec![ExitFeeTier { min_share: Decimal::zero(), fee: Decimal::percent(3) }];
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetExitFeeTiers { tiers }).unwrap();
+
+        let err = execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), unstake).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::BelowMinReceive { payout: Uint128::new(97), min_receive: Uint128::new(100) }
+        );
+        assert_eq!(STAKES.load(&deps.storage, &alice).unwrap(), Uint128::new(1_000));
+
+        let unstake = ExecuteMsg::Unstake { amount: Uint128::new(100), min_receive: Some(Uint128::new(97)), recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), unstake).unwrap();
+
+        // reveals are protected the same way
+        let rule = CommitReveal { threshold: Uint128::new(50), reveal_delay: 1 };
+        execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::SetCommitReveal { rule: Some(rule) }).unwrap();
+        let hash = unstake_commitment(Uint128::new(200), "salt");
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), ExecuteMsg::CommitUnstake { hash }).unwrap();
+        let mut later = mock_env();
+        later.block.height += 1;
+        let reveal = |min_receive| ExecuteMsg::RevealUnstake {
+            amount: Uint128::new(200),
+            salt: "salt".to_string(),
+            min_receive: Some(Uint128::new(min_receive)),
+        };
+        let err = execute(deps.as_mut(), later.clone(), message_info(&alice, &[]), reveal(195)).unwrap_err();
+        assert!(matches!(err, ContractError::BelowMinReceive { .. }));
+        execute(deps.as_mut(), later, message_info(&alice, &[]), reveal(194)).unwrap();
+        assert_eq!(STAKES.load(&deps.storage, &alice).unwrap(), Uint128::new(700));
+    }
+
+    #[test]
+    fn guardian_claims_ownership_after_owner_inactivity() {
+        let mut deps = mock_dependencies();
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        let guardian = message_info(&deps.api.addr_make("guardian"), &[]);
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        // no guardian, no claim
+        let err = execute(deps.as_mut(), mock_env(), guardian.clone(), ExecuteMsg::ClaimOwnership {}).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let new_guardian = |address: &Addr| NewGuardian {
+            address: address.to_string(),
+            inactivity: Duration::Time(1_000),
+        };
+        let msg = ExecuteMsg::SetGuardian { guardian: Some(new_guardian(&owner.sender)) };
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidGuardian { .. }));
+        for inactivity in [Duration::Time(u64::MAX), Duration::Height(u64::MAX)] {
+            let guardian = NewGuardian { address: guardian.sender.to_string(), inactivity };
+            let msg = ExecuteMsg::SetGuardian { guardian: Some(guardian) };
+            let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidGuardian { .. }));
+        }
+        let msg = ExecuteMsg::SetGuardian { guardian: Some(new_guardian(&guardian.sender)) };
+        execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        // any owner call pushes the claim back
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(900);
+        execute(deps.as_mut(), env.clone(), owner.clone(), ExecuteMsg::SetPaused { paused: false }).unwrap();
+        env.block.time = env.block.time.plus_seconds(900);
+        let err = execute(deps.as_mut(), env.clone(), guardian.clone(), ExecuteMsg::ClaimOwnership {}).unwrap_err();
+        let claimable_at = Expiration::AtTime(mock_env().block.time.plus_seconds(1_900));
+        assert_eq!(err, ContractError::OwnerActive { claimable_at });
+        let value: GuardianResponse = from_json(query(deps.as_ref(), env.clone(), QueryMsg::Guardian {}).unwrap()).unwrap();
+        assert_eq!(value.claimable_at, Some(claimable_at));
+
+        // only the guardian may claim
+        env.block.time = env.block.time.plus_seconds(100);
+        let alice = message_info(&deps.api.addr_make("alice"), &[]);
+        let err = execute(deps.as_mut(), env.clone(), alice, ExecuteMsg::ClaimOwnership {}).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res = execute(deps.as_mut(), env.clone(), guardian.clone(), ExecuteMsg::ClaimOwnership {}).unwrap();
+        assert!(res.attributes.contains(&attr("previous_owner", owner.sender.as_str())));
+        assert_eq!(STATE.load(&deps.storage).unwrap().owner, guardian.sender);
+        assert!(!GUARDIAN.exists(&deps.storage));
+        let err = execute(deps.as_mut(), env.clone(), owner, ExecuteMsg::SetPaused { paused: true }).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(deps.as_mut(), env, guardian, ExecuteMsg::SetPaused { paused: true }).unwrap();
+    }
+
+    #[test]
+    fn contract_stakers_can_require_unstake_confirmation() {
+        let mut deps = mock_dependencies();
+        let vault = deps.api.addr_make("vault");
+        let vault_addr = vault.to_string();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::ContractInfo { contract_addr } if *contract_addr == vault_addr => {
+                let info = ContractInfoResponse::new(1, Addr::unchecked("creator"), None, false, None);
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::NoSuchContract { addr: "unknown".to_string() }),
+        });
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![
+                (vault.to_string(), Uint128::new(500)),
+                (alice.to_string(), Uint128::new(10)),
+            ]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(510, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner, msg).unwrap();
+        let vault_info = message_info(&vault, &[]);
+
+        // only contracts opt in
+        let msg = ExecuteMsg::SetUnstakeConfirmation { blocks: 10 };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg.clone()).unwrap_err();
+        execute(deps.as_mut(), mock_env(), vault_info.clone(), msg).unwrap();
+
+        let unstake = ExecuteMsg::Unstake { amount: Uint128::new(200), min_receive: None, recipients: None };
+        let res = execute(deps.as_mut(), mock_env(), vault_info.clone(), unstake.clone()).unwrap();
+        assert!(res.messages.is_empty());
+        assert!(res.attributes.contains(&attr("action", "queue_unstake")));
+        assert_eq!(STAKES.load(&deps.storage, &vault).unwrap(), Uint128::new(500));
+        let err = execute(deps.as_mut(), mock_env(), vault_info.clone(), unstake.clone()).unwrap_err();
+        assert_eq!(err, ContractError::UnstakePending {});
+        let schedule = ExecuteMsg::ScheduleUnstake {
+            amount: Uint128::new(100),
+            execute_at: Expiration::AtHeight(0),
+            bounty: None,
+            condition: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), vault_info.clone(), schedule).unwrap_err();
+        assert_eq!(err, ContractError::ConfirmationRequired {});
+
+        let height = mock_env().block.height;
+        let err = execute(deps.as_mut(), mock_env(), vault_info.clone(), ExecuteMsg::ConfirmUnstake {}).unwrap_err();
+        assert_eq!(err, ContractError::UnstakeNotConfirmable { height: height + 10 });
+
+        // the vault notices the attempt and aborts it
+        execute(deps.as_mut(), mock_env(), vault_info.clone(), ExecuteMsg::AbortUnstake {}).unwrap();
+        let mut later = mock_env();
+        later.block.height += 10;
+        let err = execute(deps.as_mut(), later.clone(), vault_info.clone(), ExecuteMsg::ConfirmUnstake {}).unwrap_err();
+        assert_eq!(err, ContractError::NoPendingUnstake {});
+
+        // lifting the delay only applies once the current one has run out
+        let msg = ExecuteMsg::SetUnstakeConfirmation { blocks: 0 };
+        execute(deps.as_mut(), mock_env(), vault_info.clone(), msg).unwrap();
+        execute(deps.as_mut(), mock_env(), vault_info.clone(), unstake.clone()).unwrap();
+        let msg = QueryMsg::Portfolio { address: vault.to_string() };
+        let value: PortfolioResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            value.pending_exits,
+            vec![PendingExit::UnconfirmedUnstake {
+                denom: "token".to_string(),
+                amount: Uint128::new(200),
+                confirmable_at_height: height + 10,
+            }]
+        );
+
+        let res = execute(deps.as_mut(), later.clone(), vault_info.clone(), ExecuteMsg::ConfirmUnstake {}).unwrap();
+        assert!(res.attributes.contains(&attr("action", "confirm_unstake")));
+        assert_eq!(STAKES.load(&deps.storage, &vault).unwrap(), Uint128::new(300));
+        let res = execute(deps.as_mut(), later.clone(), vault_info.clone(), unstake).unwrap();
+        assert!(res.attributes.contains(&attr("action", "unstake")));
+        assert_eq!(STAKES.load(&deps.storage, &vault).unwrap(), Uint128::new(100));
+        // the lifted delay no longer gates orders either
+        let schedule = ExecuteMsg::ScheduleUnstake {
+            amount: Uint128::new(50),
+            execute_at: Expiration::AtHeight(later.block.height + 1),
+            bounty: None,
+            condition: None,
+        };
+        execute(deps.as_mut(), later, vault_info.clone(), schedule).unwrap();
+
+        // a delay that would outlast the chain is refused
+        let msg = ExecuteMsg::SetUnstakeConfirmation { blocks: u64::MAX };
+        execute(deps.as_mut(), mock_env(), vault_info, msg).unwrap_err();
+    }
+    #[test]
+    fn liquidity_check_reports_a_short_pool() {
+        let mut deps = mock_dependencies_with_balance(&coins(50, "token"));
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(50))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(50, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        deps.querier
+            .bank
+            .update_balance(mock_env().contract.address, coins(20, "token"));
+
+        let update = ConfigUpdate { liquidity_check: Some(true), ..Default::default() };
+        let owner = message_info(&owner.sender, &[]);
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::UpdateConfig(Box::new(update))).unwrap();
+
+        let unstake = ExecuteMsg::Unstake { amount: Uint128::new(30), min_receive: None, recipients: None };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), unstake.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientContractLiquidity {
+                denom: "token".to_string(),
+                needed: Uint128::new(30),
+                available: Uint128::new(20),
+            }
+        );
+        assert_eq!(STAKES.load(&deps.storage, &alice).unwrap(), Uint128::new(50));
+
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(20), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+
+        // with the check off, the payout is left to the bank module
+        let update = ConfigUpdate { liquidity_check: Some(false), ..Default::default() };
+        execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::UpdateConfig(Box::new(update))).unwrap();
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), unstake).unwrap();
+    }
+    #[test]
+    fn payouts_can_be_split_between_recipients() {
+        let mut deps = mock_dependencies();
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let carol = deps.api.addr_make("carol");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(200))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(200, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner, msg).unwrap();
+        let alice_info = message_info(&alice, &[]);
+
+        let third = Decimal::from_ratio(1u128, 3u128);
+        let split = |shares: Vec<(&Addr, Decimal)>| {
+            let recipients = shares.into_iter().map(|(address, share)| (address.to_string(), share)).collect();
+            ExecuteMsg::Unstake { amount: Uint128::new(100), min_receive: None, recipients: Some(recipients) }
+        };
+        let invalid = [
+            (split(vec![]), "no recipients given"),
+            (split(vec![(&alice, Decimal::percent(50)), (&bob, Decimal::percent(40))]), "shares must add up to one"),
+            (split(vec![(&alice, Decimal::one()), (&bob, Decimal::zero())]), "shares must be positive"),
+        ];
+        for (msg, reason) in invalid {
+            let err = execute(deps.as_mut(), mock_env(), alice_info.clone(), msg).unwrap_err();
+            assert_eq!(err, ContractError::InvalidRecipients { reason: reason.to_string() });
+        }
+        let msg = split(vec![(&bob, Decimal::percent(50)), (&bob, Decimal::percent(50))]);
+        let err = execute(deps.as_mut(), mock_env(), alice_info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidRecipients { reason: format!("{} is listed twice", bob) });
+
+        // the first recipient gets the rounding remainder, so nothing is lost
+        let msg = split(vec![(&alice, third), (&bob, third), (&carol, Decimal::one() - third - third)]);
+        let res = execute(deps.as_mut(), mock_env(), alice_info, msg).unwrap();
+        let sends: Vec<_> = [(&alice, 34), (&bob, 33), (&carol, 33)]
+            .into_iter()
+            .map(|(address, amount)| {
+                cosmwasm_std::CosmosMsg::Bank(cosmwasm_std::BankMsg::Send {
+                    to_address: address.to_string(),
+                    amount: coins(amount, "token"),
+                })
+            })
+            .collect();
+        assert_eq!(res.messages.iter().map(|m| m.msg.clone()).collect::<Vec<_>>(), sends);
+        assert_eq!(STAKES.load(&deps.storage, &alice).unwrap(), Uint128::new(100));
+    }
+    #[test]
+    fn owner_manages_trusted_pools() {
+        let mut deps = mock_dependencies();
+        let sibling = deps.api.addr_make("sibling");
+        let sibling_addr = sibling.to_string();
+        let other = deps.api.addr_make("other");
+        let other_addr = other.to_string();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Raw { contract_addr, .. } if *contract_addr == sibling_addr || *contract_addr == other_addr => {
+                let contract = if *contract_addr == sibling_addr { CONTRACT_NAME } else { "crates.io:cw20-base" };
+                let version = cw2::ContractVersion { contract: contract.to_string(), version: "0.2.0".to_string() };
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&version).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::NoSuchContract { addr: "unknown".to_string() }),
+        });
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+
+        let add = ExecuteMsg::AddTrustedPool { address: sibling.to_string() };
+        let alice = message_info(&deps.api.addr_make("alice"), &[]);
+        let err = execute(deps.as_mut(), mock_env(), alice, add.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), add.clone()).unwrap();
+        assert_eq!(res.events, vec![Event::new("trusted_pool_added").add_attribute("pool", sibling.as_str())]);
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), add).unwrap_err();
+        assert_eq!(err, ContractError::TrustedPoolExists { address: sibling.to_string() });
+
+        // only other pools qualify
+        let bob = deps.api.addr_make("bob");
+        for address in [bob, other, mock_env().contract.address] {
+            let msg = ExecuteMsg::AddTrustedPool { address: address.to_string() };
+            let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+            assert_eq!(err, ContractError::InvalidTargetPool { address: address.to_string() });
+        }
+
+        let msg = QueryMsg::TrustedPools { start_after: None, limit: None };
+        let value: TrustedPoolsResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            value.pools,
+            vec![TrustedPoolEntry {
+                address: sibling.to_string(),
+                added_at_height: mock_env().block.height,
+            }]
+        );
+        let msg = QueryMsg::TrustedPools { start_after: Some(sibling.to_string()), limit: None };
+        let value: TrustedPoolsResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(value.pools.is_empty());
+
+        let remove = ExecuteMsg::RemoveTrustedPool { address: sibling.to_string() };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), remove.clone()).unwrap();
+        assert_eq!(res.events, vec![Event::new("trusted_pool_removed").add_attribute("pool", sibling.as_str())]);
+        let err = execute(deps.as_mut(), mock_env(), owner, remove).unwrap_err();
+        assert_eq!(err, ContractError::UnknownTrustedPool { address: sibling.to_string() });
+        assert!(TRUSTED_POOLS.is_empty(&deps.storage));
+    }
+    #[test]
+    fn list_queries_report_malformed_records() {
+        let mut deps = mock_dependencies();
+        let stakers: Vec<_> = ["alice", "bob", "carol"].map(|name| deps.api.addr_make(name)).into();
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(stakers.iter().map(|staker| (staker.to_string(), Uint128::new(10))).collect()),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(30, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        // a record left in a shape the current code can't read
+        let mut sorted = stakers.clone();
+        sorted.sort();
+        let corrupted = &sorted[1];
+        deps.storage.set(&STAKES.key(corrupted), br#"{"legacy":true}"#);
+
+        let msg = QueryMsg::Violations { start_after: None, limit: Some(2) };
+        let value: ViolationsResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(value.malformed.len(), 1);
+        assert_eq!(value.malformed[0].key, HexBinary::from(corrupted.as_bytes()));
+        // paging resumes after the malformed record
+        assert_eq!(value.last_scanned, Some(corrupted.to_string()));
+        let msg = QueryMsg::Violations { start_after: value.last_scanned, limit: Some(2) };
+        let value: ViolationsResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(value.malformed.is_empty());
+        assert_eq!(value.last_scanned, None);
+
+        // healthy responses don't carry the field at all
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Distribution {}).unwrap();
+        assert!(!String::from_utf8(res.to_vec()).unwrap().contains("malformed"));
+    }
+    #[test]
+    fn ledger_balances_after_every_flow() {
+        let mut deps = mock_dependencies_with_balance(&coins(100 + 10 + 50 - 45 - 5 - 3, "token"));
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(100))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(100, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let owner = message_info(&owner.sender, &[]);
+
+        let tiers = vec![ExitFeeTier { min_share: Decimal::percent(1), fee: Decimal::percent(10) }];
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetExitFeeTiers { tiers }).unwrap();
+        let rates = RebateRates { stake: Uint128::new(3), unstake: Uint128::zero() };
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetRebateRates { rates: Some(rates) }).unwrap();
+        let owner_funded = message_info(&owner.sender, &coins(10, "token"));
+        execute(deps.as_mut(), mock_env(), owner_funded, ExecuteMsg::FundRebates {}).unwrap();
+
+        let stake = ExecuteMsg::Stake { amount: Uint128::new(50) };
+        execute(deps.as_mut(), mock_env(), message_info(&bob, &coins(50, "token")), stake).unwrap();
+        // alice pays a 5 fee on her way out
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(50), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+        execute(deps.as_mut(), mock_env(), message_info(&bob, &[]), ExecuteMsg::ClaimRebates { recipients: None })
+            .unwrap();
+
+        let value: TrialBalanceResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::TrialBalance {}).unwrap()).unwrap();
+        let balances: Vec<_> = value.accounts.iter().map(|entry| (entry.account, entry.balance)).collect();
+        assert_eq!(
+            balances,
+            vec![
+                (LedgerAccount::Cash, Int256::from(100 + 10 + 50 - 45 - 5 - 3)),
+                (LedgerAccount::Principal, Int256::from(-100)),
+                (LedgerAccount::RewardsPayable, Int256::from(-7)),
+                (LedgerAccount::FeesPayable, Int256::zero()),
+                (LedgerAccount::Treasury, Int256::zero()),
+            ]
+        );
+        assert_eq!(value.principal_difference, Int256::zero());
+        assert_eq!(value.cash_difference, Int256::zero());
+
+        // stakes and funds that moved behind the ledger's back show up as differences
+        TOTAL_STAKED.save(&mut deps.storage, &Uint128::new(90)).unwrap();
+        deps.querier.bank.update_balance(mock_env().contract.address, coins(120, "token"));
+        let value: TrialBalanceResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::TrialBalance {}).unwrap()).unwrap();
+        assert_eq!(value.principal_difference, Int256::from(10));
+        assert_eq!(value.cash_difference, Int256::from(-13));
+    }
+    #[test]
+    fn hashed_stakes_list_balances_without_addresses() {
+        let mut deps = mock_dependencies();
+        let stakers: Vec<_> = ["alice", "bob", "carol"].map(|name| deps.api.addr_make(name)).into();
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(
+                stakers.iter().zip([10, 20, 30]).map(|(staker, amount)| (staker.to_string(), Uint128::new(amount))).collect(),
+            ),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(60, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let owner = message_info(&owner.sender, &[]);
+
+        let page = |deps: Deps, start_after| {
+            let msg = QueryMsg::HashedStakes { start_after, limit: Some(2) };
+            query(deps, mock_env(), msg).map(|res| from_json::<HashedStakesResponse>(res).unwrap())
+        };
+        let err = page(deps.as_ref(), None).unwrap_err();
+        assert_eq!(err.to_string(), "Generic error: hashed staker enumeration is not enabled");
+
+        // turned on by indexing every staker, not by a config flag
+        let update = ConfigUpdate { hashed_enumeration: Some(true), ..Default::default() };
+        let msg = ExecuteMsg::UpdateConfig(Box::new(update));
+        execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+        let index = ExecuteMsg::Rebuild { kind: RebuildKind::StakerHashes, limit: Some(2) };
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetPaused { paused: true }).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), index.clone()).unwrap();
+        page(deps.as_ref(), None).unwrap_err();
+        execute(deps.as_mut(), mock_env(), owner.clone(), index.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetPaused { paused: false }).unwrap();
+
+        // listed in hash order, keyed by the stored salt
+        let salt = STAKER_HASH_SALT.load(&deps.storage).unwrap();
+        let mut expected: Vec<_> = stakers
+            .iter()
+            .zip([10, 20, 30])
+            .map(|(staker, amount)| HashedStakeEntry {
+                staker_hash: staker_hash(&salt, staker).into(),
+                amount: Uint128::new(amount),
+            })
+            .collect();
+        expected.sort_by(|a, b| a.staker_hash.cmp(&b.staker_hash));
+        let first = page(deps.as_ref(), None).unwrap();
+        assert_eq!(first.stakes, expected[..2]);
+        assert_eq!(first.last_scanned, Some(expected[1].staker_hash.clone()));
+        let second = page(deps.as_ref(), first.last_scanned).unwrap();
+        assert_eq!(second.stakes, expected[2..]);
+        assert_eq!(second.last_scanned, None);
+        for entry in &expected {
+            let listed = String::from_utf8(to_json_binary(entry).unwrap().to_vec()).unwrap();
+            assert!(stakers.iter().all(|staker| !listed.contains(staker.as_str())));
+        }
+
+        // violations page by hash too, so their cursor doesn't give the address order away
+        let msg = QueryMsg::Violations { start_after: None, limit: Some(1) };
+        let value: ViolationsResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(value.last_scanned, Some(expected[0].staker_hash.to_hex()));
+
+        // the index follows stakers joining and leaving
+        let dave = deps.api.addr_make("dave");
+        let stake = ExecuteMsg::Stake { amount: Uint128::new(40) };
+        execute(deps.as_mut(), mock_env(), message_info(&dave, &coins(40, "token")), stake).unwrap();
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(10), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&stakers[0], &[]), msg).unwrap();
+        let msg = QueryMsg::HashedStakes { start_after: None, limit: None };
+        let value: HashedStakesResponse = from_json(query(deps.as_ref(), mock_env(), msg.clone()).unwrap()).unwrap();
+        let mut listed: Vec<_> = value.stakes.iter().map(|entry| entry.amount.u128()).collect();
+        listed.sort();
+        assert_eq!(listed, vec![20, 30, 40]);
+
+        // reindexing draws a new salt and leaves the old entries behind
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetPaused { paused: true }).unwrap();
+        let mut next_block = mock_env();
+        next_block.block.height += 1;
+        let index = ExecuteMsg::Rebuild { kind: RebuildKind::StakerHashes, limit: None };
+        execute(deps.as_mut(), next_block, owner.clone(), index).unwrap();
+        let rehashed: HashedStakesResponse = from_json(query(deps.as_ref(), mock_env(), msg.clone()).unwrap()).unwrap();
+        assert_eq!(rehashed.stakes.len(), 3);
+        assert!(rehashed.stakes.iter().all(|entry| !value.stakes.contains(entry)));
+
+        // a malformed record is reported under its hash too
+        deps.storage.set(&STAKES.key(&dave), br#"{"legacy":true}"#);
+        let value: HashedStakesResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(value.stakes.len(), 2);
+        let salt = STAKER_HASH_SALT.load(&deps.storage).unwrap();
+        assert_eq!(value.malformed[0].key, HexBinary::from(staker_hash(&salt, &dave)));
+
+        let update = ConfigUpdate { hashed_enumeration: Some(false), ..Default::default() };
+        execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::UpdateConfig(Box::new(update))).unwrap();
+        assert!(!STAKER_HASH_SALT.exists(&deps.storage));
+    }
+    #[test]
+    fn recovery_mode_withdrawals_pay_no_exit_fee() {
+        let mut deps = mock_dependencies();
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(100))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(100, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let owner = message_info(&owner.sender, &[]);
+        let tiers = vec![ExitFeeTier { min_share: Decimal::percent(1), fee: Decimal::percent(10) }];
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetExitFeeTiers { tiers }).unwrap();
+
+        let mut later = mock_env();
+        later.block.time = later.block.time.plus_seconds(60);
+        let msg = ExecuteMsg::ScheduleUnstake {
+            amount: Uint128::new(30),
+            execute_at: Expiration::AtTime(later.block.time),
+            bounty: None,
+            condition: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetPaused { paused: true }).unwrap();
+        execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::SetRecoveryMode { enabled: true }).unwrap();
+
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(40), min_receive: None, recipients: None };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+        assert!(res.attributes.contains(&attr("fee", "0")));
+        assert_eq!(res.messages.len(), 1);
+
+        let keeper = message_info(&deps.api.addr_make("keeper"), &[]);
+        let res = execute(deps.as_mut(), later, keeper, ExecuteMsg::ExecuteUnstakeOrder { id: 0 }).unwrap();
+        assert!(res.attributes.contains(&attr("fee", "0")));
+        assert!(res.attributes.contains(&attr("payout", "30")));
+        assert_eq!(STAKES.load(&deps.storage, &alice).unwrap(), Uint128::new(30));
+    }
+    #[test]
+    fn recovery_mode_withdrawals_earn_no_rebate() {
+        let mut deps = mock_dependencies();
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(100))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(100, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let owner = message_info(&owner.sender, &[]);
+        let rates = RebateRates { stake: Uint128::zero(), unstake: Uint128::new(2) };
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetRebateRates { rates: Some(rates) }).unwrap();
+        let owner_funded = message_info(&owner.sender, &coins(10, "token"));
+        execute(deps.as_mut(), mock_env(), owner_funded, ExecuteMsg::FundRebates {}).unwrap();
+
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetPaused { paused: true }).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetRecoveryMode { enabled: true }).unwrap();
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(40), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+        assert!(REBATE_ACCOUNTS.may_load(&deps.storage, &alice).unwrap().is_none());
+        assert_eq!(REBATE_POOL.load(&deps.storage).unwrap(), Uint128::new(10));
+
+        // once unpaused, unstakes earn it again
+        execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::SetPaused { paused: false }).unwrap();
+        let msg = ExecuteMsg::Unstake { amount: Uint128::new(10), min_receive: None, recipients: None };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+        let account = REBATE_ACCOUNTS.load(&deps.storage, &alice).unwrap();
+        assert_eq!((account.unstakes, account.accrued), (1, Uint128::new(2)));
+    }
+    #[test]
+    fn large_orders_need_commit_and_reveal() {
+        let mut deps = mock_dependencies();
+        let alice = deps.api.addr_make("alice");
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(alice.to_string(), Uint128::new(1_000))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(1_000, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+        let owner = message_info(&owner.sender, &[]);
+
+        let mut later = mock_env();
+        later.block.time = later.block.time.plus_seconds(60);
+        let schedule = |amount| ExecuteMsg::ScheduleUnstake {
+            amount: Uint128::new(amount),
+            execute_at: Expiration::AtTime(later.block.time),
+            bounty: None,
+            condition: None,
+        };
+        // placed before the rule applied
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), schedule(500)).unwrap();
+
+        let rule = CommitReveal { threshold: Uint128::new(100), reveal_delay: 3 };
+        execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::SetCommitReveal { rule: Some(rule) }).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), schedule(500)).unwrap_err();
+        assert_eq!(err, ContractError::CommitRequired { threshold: Uint128::new(100) });
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), schedule(100)).unwrap();
+
+        let keeper = message_info(&deps.api.addr_make("keeper"), &[]);
+        let err = execute(deps.as_mut(), later.clone(), keeper.clone(), ExecuteMsg::ExecuteUnstakeOrder { id: 0 })
+            .unwrap_err();
+        assert_eq!(err, ContractError::CommitRequired { threshold: Uint128::new(100) });
+        execute(deps.as_mut(), later, keeper, ExecuteMsg::ExecuteUnstakeOrder { id: 1 }).unwrap();
+        assert_eq!(STAKES.load(&deps.storage, &alice).unwrap(), Uint128::new(900));
+    }
+    #[test]
+    fn orders_wait_for_a_later_confirmation_opt_in() {
+        let mut deps = mock_dependencies();
+        let vault = deps.api.addr_make("vault");
+        let vault_addr = vault.to_string();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::ContractInfo { contract_addr } if *contract_addr == vault_addr => {
+                let info = ContractInfoResponse::new(1, Addr::unchecked("creator"), None, false, None);
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::NoSuchContract { addr: "unknown".to_string() }),
+        });
+        let msg = InstantiateMsg {
+            count: 0,
+            initial_stakes: Some(vec![(vault.to_string(), Uint128::new(500))]),
+            asset_metadata: None,
+        };
+        let owner = message_info(&deps.api.addr_make("creator"), &coins(500, "token"));
+        instantiate(deps.as_mut(), mock_env(), owner, msg).unwrap();
+        let vault_info = message_info(&vault, &[]);
+
+        // an order placed with the vault's key before it opts in
+        let schedule = ExecuteMsg::ScheduleUnstake {
+            amount: Uint128::new(100),
+            execute_at: Expiration::AtHeight(mock_env().block.height + 5),
+            bounty: None,
+            condition: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), vault_info.clone(), schedule).unwrap();
+        let id: u64 = res.attributes.iter().find(|a| a.key == "order_id").unwrap().value.parse().unwrap();
+        let msg = ExecuteMsg::SetUnstakeConfirmation { blocks: 10 };
+        execute(deps.as_mut(), mock_env(), vault_info.clone(), msg).unwrap();
+
+        let mut later = mock_env();
+        later.block.height += 5;
+        let keeper = message_info(&deps.api.addr_make("keeper"), &[]);
+        let err = execute(deps.as_mut(), later, keeper, ExecuteMsg::ExecuteUnstakeOrder { id }).unwrap_err();
+        assert_eq!(err, ContractError::ConfirmationRequired {});
+        assert_eq!(STAKES.load(&deps.storage, &vault).unwrap(), Uint128::new(500));
+        execute(deps.as_mut(), mock_env(), vault_info, ExecuteMsg::CancelUnstakeOrder { id }).unwrap();
+    }
 }
@@ -1,4 +1,5 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
+use cw_utils::Expiration;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -8,6 +9,177 @@ pub enum ContractError {
 
     #[error("Unauthorized")]
     Unauthorized {},
+
+    #[error("Owner is still active; the guardian can claim ownership {claimable_at}")]
+    OwnerActive { claimable_at: Expiration },
+
+    #[error("Invalid guardian: {reason}")]
+    InvalidGuardian { reason: String },
+
+    #[error("Contract is paused")]
+    Paused {},
+
+    #[error("This message does not accept funds")]
+    UnexpectedFunds {},
+
+    #[error("Deposits closed at {closed_at}")]
+    DepositsClosed { closed_at: Expiration },
+
+    #[error("Invalid deposits deadline: {reason}")]
+    InvalidDepositsCloseAt { reason: String },
+
+    #[error("Contract must be paused first")]
+    NotPaused {},
+
+    #[error("Stakes are frozen until the {kind} rebuild completes")]
+    Rebuilding { kind: String },
+
+    #[error("Pool funds were evacuated to escrow; the pool is frozen")]
+    Evacuated {},
+
+    #[error("No escrow contract is registered")]
+    NoEscrow {},
+
+    #[error("Escrow {address} is not a contract")]
+    InvalidEscrow { address: String },
+
+    #[error("Cannot migrate stake to {address}: not another pool contract")]
+    InvalidTargetPool { address: String },
+
+    #[error("Cannot send a stake receipt to {address}: not a contract")]
+    ReceiptNotContract { address: String },
+
+    #[error("{address} is already a trusted pool")]
+    TrustedPoolExists { address: String },
+
+    #[error("{address} is not a trusted pool")]
+    UnknownTrustedPool { address: String },
+
+    #[error("Only the staking denom can be sent")]
+    InvalidDenom {},
+
+    #[error("Attached funds {received} do not match the expected {expected}")]
+    FundsMismatch { expected: Uint128, received: Uint128 },
+
+    #[error("No pending imported stake")]
+    NoPendingImport {},
+
+    #[error("Imported stake can't be clawed back until {deadline}")]
+    ClawbackNotDue { deadline: Expiration },
+
+    #[error("Contract address {address} cannot stake in this pool")]
+    ContractStakerRejected { address: String },
+
+    #[error("Only stakers can do this")]
+    NotStaker {},
+
+    #[error("Invalid profile: {reason}")]
+    InvalidProfile { reason: String },
+
+    #[error("Invalid pool metadata: {reason}")]
+    InvalidPoolMetadata { reason: String },
+
+    #[error("Invalid payout recipients: {reason}")]
+    InvalidRecipients { reason: String },
+
+    #[error("Invalid fee discount tiers: {reason}")]
+    InvalidFeeDiscountTiers { reason: String },
+
+    #[error("Payout {payout} is below the requested minimum of {min_receive}")]
+    BelowMinReceive { payout: Uint128, min_receive: Uint128 },
+
+    #[error("An unstake is already waiting for confirmation")]
+    UnstakePending {},
+
+    #[error("No unstake is waiting for confirmation")]
+    NoPendingUnstake {},
+
+    #[error("Unstake can be confirmed from height {height}")]
+    UnstakeNotConfirmable { height: u64 },
+
+    #[error("Scheduled unstakes bypass confirmations, which this staker requires")]
+    ConfirmationRequired {},
+
+    #[error("Pool holds {available}{denom}, {needed}{denom} needed")]
+    InsufficientContractLiquidity { denom: String, needed: Uint128, available: Uint128 },
+
+    #[error("Invalid exit fee tiers: {reason}")]
+    InvalidExitFeeTiers { reason: String },
+
+    #[error("Unstaking more than {threshold} requires a commit and reveal")]
+    CommitRequired { threshold: Uint128 },
+
+    #[error("No unstake commit to reveal")]
+    NoUnstakeCommit {},
+
+    #[error("Unstake commit can't be revealed before height {height}")]
+    RevealTooEarly { height: u64 },
+
+    #[error("Revealed amount and salt do not match the commit")]
+    InvalidReveal {},
+
+    #[error("Invalid commit-reveal rule: {reason}")]
+    InvalidCommitReveal { reason: String },
+
+    #[error("Invalid deposit lock: {reason}")]
+    InvalidDepositLock { reason: String },
+
+    #[error("Recent deposits stay locked until height {unlocks_at}")]
+    DepositLocked { unlocks_at: u64 },
+
+    #[error("No signing key is registered for the signer")]
+    NoSigningKey {},
+
+    #[error("Invalid signature")]
+    InvalidSignature {},
+
+    #[error("Invalid nonce, expected {expected}")]
+    InvalidNonce { expected: u64 },
+
+    #[error("Signed payload is for another chain, contract or action")]
+    WrongSigningDomain {},
+
+    #[error("Unstake order {id} not found")]
+    OrderNotFound { id: u64 },
+
+    #[error("Unstake order can't be executed until {execute_at}")]
+    OrderNotDue { execute_at: Expiration },
+
+    #[error("Condition of unstake order {id} does not hold")]
+    OrderConditionNotMet { id: u64 },
+
+    #[error("Invalid unstake order: {reason}")]
+    InvalidOrder { reason: String },
+
+    #[error("No rebates to claim")]
+    NoRebates {},
+
+    #[error("Faucet available again {available_at}")]
+    FaucetCooldown { available_at: Expiration },
+
+    #[error("Faucet has no surplus funds to give out")]
+    FaucetEmpty {},
+
+    #[error("Invalid TVL alert: {reason}")]
+    InvalidTvlAlert { reason: String },
+
+    #[error("Arithmetic overflow while updating {ledger}")]
+    Overflow { ledger: String },
+
+    #[error("Arithmetic underflow while updating {ledger}")]
+    Underflow { ledger: String },
+
+    #[error("Cannot migrate from contract {contract}")]
+    InvalidMigrationContract { contract: String },
+
+    #[error("Invalid contract version {version}")]
+    InvalidVersion { version: String },
+
+    #[error("Cannot migrate from version {from} down to {to}")]
+    MigrationDowngrade { from: String, to: String },
+
+    #[error("No migration path from version {from}")]
+    UnsupportedMigration { from: String },
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
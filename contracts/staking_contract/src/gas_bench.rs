@@ -0,0 +1,149 @@
+//! Storage gas benchmarks of the hot handlers, run with `cargo gas-bench`.
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+use cosmwasm_std::testing::{message_info, mock_env, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{coins, Empty, Order, OwnedDeps, Record, Storage, Uint128};
+
+use crate::contract::execute;
+use crate::fixtures::Fixture;
+use crate::msg::ExecuteMsg;
+use crate::state::RebateRates;
+
+// Cosmos SDK KVStore gas schedule (store/types/gas.go, KVGasConfig)
+const DELETE_COST: u64 = 1_000;
+const READ_COST_FLAT: u64 = 1_000;
+const READ_COST_PER_BYTE: u64 = 3;
+const WRITE_COST_FLAT: u64 = 2_000;
+const WRITE_COST_PER_BYTE: u64 = 30;
+const ITER_NEXT_COST_FLAT: u64 = 30;
+
+/// MockStorage that charges storage gas the way the SDK's GasKVStore does.
+#[derive(Default)]
+struct MeteredStorage {
+    inner: MockStorage,
+    gas: Cell<u64>,
+}
+
+impl MeteredStorage {
+    fn charge(&self, amount: u64) {
+        self.gas.set(self.gas.get() + amount);
+    }
+
+    fn take_gas(&self) -> u64 {
+        self.gas.replace(0)
+    }
+}
+
+impl Storage for MeteredStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let value = self.inner.get(key);
+        let len = value.as_ref().map(Vec::len).unwrap_or_default();
+        self.charge(READ_COST_FLAT + READ_COST_PER_BYTE * (key.len() + len) as u64);
+        value
+    }
+
+    fn range<'a>(
+        &'a self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Record> + 'a> {
+        Box::new(self.inner.range(start, end, order).inspect(move |(key, value)| {
+            self.charge(ITER_NEXT_COST_FLAT + READ_COST_PER_BYTE * (key.len() + value.len()) as u64);
+        }))
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.charge(WRITE_COST_FLAT + WRITE_COST_PER_BYTE * (key.len() + value.len()) as u64);
+        self.inner.set(key, value);
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.charge(DELETE_COST);
+        self.inner.remove(key);
+    }
+}
+
+type MeteredDeps = OwnedDeps<MeteredStorage, MockApi, MockQuerier, Empty>;
+
+fn metered_deps() -> MeteredDeps {
+    OwnedDeps {
+        storage: MeteredStorage::default(),
+        api: MockApi::default(),
+        querier: MockQuerier::default(),
+        custom_query_type: PhantomData,
+    }
+}
+
+/// Builds a pool with `stakers` existing positions and history, and resets the meter.
+fn pool_with_stakers(stakers: usize) -> MeteredDeps {
+    let mut deps = metered_deps();
+    Fixture::new(7)
+        .stakers(stakers)
+        .stake_range(500, 1_500)
+        .history_days(30)
+        .unstake_orders(stakers / 10)
+        .profiles(stakers / 10)
+        .build(deps.as_mut());
+    deps.storage.take_gas();
+    deps
+}
+
+fn measure(deps: &mut MeteredDeps, sender: &str, funds: u128, msg: ExecuteMsg) -> u64 {
+    let funds = if funds == 0 { vec![] } else { coins(funds, "token") };
+    let info = message_info(&deps.api.addr_make(sender), &funds);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    deps.storage.take_gas()
+}
+
+// Storage gas budgets per call. Bump deliberately, with a reason, if a change needs more.
+// 40k -> 50k: per-user interaction stats add a read and a write to every stake and unstake
+// 50k -> 60k: the double-entry ledger adds another read and write to both
+const STAKE_BUDGET: u64 = 60_000;
+const UNSTAKE_BUDGET: u64 = 60_000;
+const CLAIM_REBATES_BUDGET: u64 = 30_000;
+// allowed drift between the smallest and largest pool
+const POOL_SIZE_TOLERANCE: u64 = 1_000;
+
+#[test]
+fn handler_gas_stays_within_budget_and_flat_in_pool_size() {
+    let mut results = vec![];
+    // fixture stakes of 500..=1500 put other stakers in the bench account's buckets, so
+    // bucket entries are rewritten rather than deleted and the runs are comparable
+    for stakers in [10, 100, 1_000] {
+        let mut deps = pool_with_stakers(stakers);
+        let stake = measure(&mut deps, "bench", 500, ExecuteMsg::Stake { amount: Uint128::new(500) });
+        let top_up = measure(&mut deps, "bench", 500, ExecuteMsg::Stake { amount: Uint128::new(500) });
+        let unstake = measure(&mut deps, "bench", 0, ExecuteMsg::Unstake { amount: Uint128::new(400), min_receive: None, recipients: None });
+        let exit = measure(&mut deps, "bench", 0, ExecuteMsg::Unstake { amount: Uint128::new(600), min_receive: None, recipients: None });
+        // rebates are off above; turn them on and have another staker earn one to claim
+        let rates = RebateRates { stake: Uint128::new(2), unstake: Uint128::new(1) };
+        measure(&mut deps, "creator", 0, ExecuteMsg::SetRebateRates { rates: Some(rates) });
+        measure(&mut deps, "creator", 100, ExecuteMsg::FundRebates {});
+        measure(&mut deps, "claimer", 500, ExecuteMsg::Stake { amount: Uint128::new(500) });
+        let claim = measure(&mut deps, "claimer", 0, ExecuteMsg::ClaimRebates { recipients: None });
+
+        for gas in [stake, top_up] {
+            assert!(gas <= STAKE_BUDGET, "stake used {} gas, budget {}", gas, STAKE_BUDGET);
+        }
+        for gas in [unstake, exit] {
+            assert!(gas <= UNSTAKE_BUDGET, "unstake used {} gas, budget {}", gas, UNSTAKE_BUDGET);
+        }
+        assert!(claim <= CLAIM_REBATES_BUDGET, "claim used {} gas, budget {}", claim, CLAIM_REBATES_BUDGET);
+        results.push((stake, top_up, unstake, exit, claim));
+    }
+
+    // no handler may iterate over stakers, so costs must not depend on pool size; only the
+    // JSON encoding of totals and bucket counts gets a few bytes longer
+    let (first, last) = (results[0], results[results.len() - 1]);
+    for (small, large) in [(first.0, last.0), (first.1, last.1), (first.2, last.2), (first.3, last.3), (first.4, last.4)] {
+        assert!(
+            large <= small + POOL_SIZE_TOLERANCE,
+            "gas grew with pool size: {} -> {}",
+            small,
+            large
+        );
+    }
+}
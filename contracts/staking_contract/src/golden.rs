@@ -0,0 +1,151 @@
+//! Golden-file tests of the JSON every query returns.
+//!
+//! Generated clients (e.g. TypeScript) are built from these payloads, so a renamed field,
+//! reordered struct or retagged enum is a breaking change even when the Rust side still compiles.
+//! Each query's response for a fixed pool is compared byte for byte with `golden/<name>.json`.
+//! After an intentional change, regenerate the files with
+//! `UPDATE_GOLDEN=1 cargo test --lib golden` and review the diff.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{Decimal, OwnedDeps, Uint128};
+use cw_utils::Duration;
+
+use crate::contract::{execute, query};
+use crate::fixtures::{Built, Fixture};
+use crate::msg::{ConfigUpdate, ExecuteMsg, NewGuardian, QueryMsg};
+use crate::state::{AssetMetadata, FeeDiscountTier, PoolMetadata, RebateRates, RebuildKind, TRUSTED_POOLS};
+
+fn pool() -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Built) {
+    let mut deps = mock_dependencies();
+    let built = Fixture::new(42)
+        .stakers(4)
+        .history_days(2)
+        .unstake_orders(1)
+        .pending_imports(1)
+        .profiles(1)
+        .build(deps.as_mut());
+
+    let owner = message_info(&built.owner, &[]);
+    let update = ConfigUpdate {
+        fee_discount_tiers: Some(vec![FeeDiscountTier {
+            min_stake: Uint128::new(100),
+            discount: Decimal::percent(10),
+        }]),
+        asset_metadata: Some(AssetMetadata {
+            symbol: "DAD".to_string(),
+            decimals: 6,
+            logo_uri: None,
+        }),
+        rebate_rates: Some(RebateRates {
+            stake: Uint128::new(1),
+            unstake: Uint128::zero(),
+        }),
+        pool_metadata: Some(PoolMetadata {
+            name: "Golden pool".to_string(),
+            description: None,
+            website: Some("https://example.com".to_string()),
+            logo_uri: None,
+        }),
+        ..Default::default()
+    };
+    execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::UpdateConfig(Box::new(update))).unwrap();
+    let guardian = NewGuardian {
+        address: deps.api.addr_make("guardian").to_string(),
+        inactivity: Duration::Height(1_000),
+    };
+    let msg = ExecuteMsg::SetGuardian { guardian: Some(guardian) };
+    execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+    execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::SetPaused { paused: true }).unwrap();
+    let msg = ExecuteMsg::Rebuild { kind: RebuildKind::StakerHashes, limit: None };
+    execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap();
+    execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::SetPaused { paused: false }).unwrap();
+    // the mock querier knows no contracts for `AddTrustedPool` to check
+    let sibling = deps.api.addr_make("sibling_pool");
+    TRUSTED_POOLS.save(&mut deps.storage, &sibling, &mock_env().block.height).unwrap();
+    (deps, built)
+}
+
+fn cases(built: &Built) -> Vec<(&'static str, QueryMsg)> {
+    let staker = built.stakers[0].to_string();
+    vec![
+        ("count", QueryMsg::Count {}),
+        ("stake", QueryMsg::Stake { address: staker.clone() }),
+        ("stakes", QueryMsg::Stakes { addresses: vec![staker.clone(), built.stakers[1].to_string()] }),
+        ("share_of_pool", QueryMsg::ShareOfPool { address: staker.clone() }),
+        ("asset_info", QueryMsg::AssetInfo {}),
+        ("config", QueryMsg::Config {}),
+        ("distribution", QueryMsg::Distribution {}),
+        ("metrics", QueryMsg::Metrics {}),
+        ("daily_stats", QueryMsg::DailyStats { start_day: None, end_day: None, limit: None }),
+        ("fee_discount", QueryMsg::FeeDiscount { address: staker.clone() }),
+        (
+            "has_min_stake",
+            QueryMsg::HasMinStake { address: staker.clone(), amount: Uint128::new(1), min_duration: Duration::Time(0) },
+        ),
+        ("pending_imports", QueryMsg::PendingImports { start_after: None, limit: None }),
+        ("unstake_orders", QueryMsg::UnstakeOrders { start_after: None, limit: None }),
+        ("violations", QueryMsg::Violations { start_after: None, limit: None }),
+        ("profiles", QueryMsg::Profiles { start_after: None, limit: None }),
+        ("trusted_pools", QueryMsg::TrustedPools { start_after: None, limit: None }),
+        ("hashed_stakes", QueryMsg::HashedStakes { start_after: None, limit: None }),
+        ("health", QueryMsg::Health {}),
+        ("trial_balance", QueryMsg::TrialBalance {}),
+        ("rebates", QueryMsg::Rebates { address: staker.clone() }),
+        ("user_stats", QueryMsg::UserStats { address: staker.clone() }),
+        ("portfolio", QueryMsg::Portfolio { address: built.imported[0].to_string() }),
+        ("nonce", QueryMsg::Nonce { address: staker.clone() }),
+        ("guardian", QueryMsg::Guardian {}),
+        (
+            "aggregate",
+            QueryMsg::Aggregate { queries: vec![QueryMsg::Count {}, QueryMsg::Stake { address: staker }] },
+        ),
+    ]
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("golden").join(format!("{name}.json"))
+}
+
+#[test]
+fn query_responses_match_golden_files() {
+    let (deps, built) = pool();
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut mismatched = vec![];
+    for (name, msg) in cases(&built) {
+        let response = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let actual = format!("{}\n", String::from_utf8(response.to_vec()).unwrap());
+        let path = golden_path(name);
+        if update {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_default();
+        if actual != expected {
+            mismatched.push(format!("{name}:\n  expected {}  actual   {}", expected, actual));
+        }
+    }
+    assert!(
+        mismatched.is_empty(),
+        "responses changed; rerun with UPDATE_GOLDEN=1 if intended\n{}",
+        mismatched.join("\n")
+    );
+}
+
+/// Every query must have a golden file, so new queries can't slip in without one.
+#[test]
+fn every_query_has_a_golden_case() {
+    let (_, built) = pool();
+    let covered: HashSet<_> = cases(&built)
+        .into_iter()
+        .map(|(_, msg)| std::mem::discriminant(&msg))
+        .collect();
+    let schema = cosmwasm_schema::schema_for!(QueryMsg);
+    let variants = schema.schema.subschemas.and_then(|subschemas| subschemas.one_of).unwrap();
+    assert_eq!(covered.len(), variants.len(), "add the new query to `cases`");
+}
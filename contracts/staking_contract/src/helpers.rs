@@ -2,11 +2,16 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{
-    to_json_binary, Addr, CosmosMsg, CustomQuery, Querier, QuerierWrapper, StdResult, WasmMsg,
-    WasmQuery,
+    to_json_binary, Addr, CosmosMsg, CustomQuery, HexBinary, Querier, QuerierWrapper, StdResult,
+    Uint128, WasmMsg, WasmQuery,
 };
+use cw_utils::Duration;
+use sha2::{Digest, Sha256};
 
-use crate::msg::{ExecuteMsg, GetCountResponse, QueryMsg};
+use crate::msg::{
+    ExecuteMsg, GetCountResponse, GetStakeResponse, HasMinStakeResponse, MetricsResponse, QueryMsg,
+    ShareOfPoolResponse,
+};
 
 /// CwTemplateContract is a wrapper around Addr that provides a lot of helpers
 /// for working with this.
@@ -35,7 +40,7 @@ impl CwTemplateContract {
         T: Into<String>,
         CQ: CustomQuery,
     {
-        let msg = QueryMsg::GetCount {};
+        let msg = QueryMsg::Count {};
         let query = WasmQuery::Smart {
             contract_addr: self.addr().into(),
             msg: to_json_binary(&msg)?,
@@ -45,3 +50,68 @@ impl CwTemplateContract {
         Ok(res)
     }
 }
+
+/// Queries of a pool at `contract` as one-liners for integrating contracts, e.g.
+/// `deps.querier.dad_stake(&pool, &staker)?`.
+pub trait DadStakingQuerier {
+    fn dad_stake(&self, contract: impl Into<String>, address: impl Into<String>) -> StdResult<Uint128>;
+
+    fn dad_total_staked(&self, contract: impl Into<String>) -> StdResult<Uint128>;
+
+    fn dad_share_of_pool(
+        &self,
+        contract: impl Into<String>,
+        address: impl Into<String>,
+    ) -> StdResult<ShareOfPoolResponse>;
+
+    /// Whether `address` has held at least `amount` for at least `min_duration`
+    fn dad_has_min_stake(
+        &self,
+        contract: impl Into<String>,
+        address: impl Into<String>,
+        amount: Uint128,
+        min_duration: Duration,
+    ) -> StdResult<bool>;
+}
+
+impl<C: CustomQuery> DadStakingQuerier for QuerierWrapper<'_, C> {
+    fn dad_stake(&self, contract: impl Into<String>, address: impl Into<String>) -> StdResult<Uint128> {
+        let msg = QueryMsg::Stake { address: address.into() };
+        let res: GetStakeResponse = self.query_wasm_smart(contract, &msg)?;
+        Ok(res.amount)
+    }
+
+    fn dad_total_staked(&self, contract: impl Into<String>) -> StdResult<Uint128> {
+        let res: MetricsResponse = self.query_wasm_smart(contract, &QueryMsg::Metrics {})?;
+        Ok(res.total_staked)
+    }
+
+    fn dad_share_of_pool(
+        &self,
+        contract: impl Into<String>,
+        address: impl Into<String>,
+    ) -> StdResult<ShareOfPoolResponse> {
+        self.query_wasm_smart(contract, &QueryMsg::ShareOfPool { address: address.into() })
+    }
+
+    fn dad_has_min_stake(
+        &self,
+        contract: impl Into<String>,
+        address: impl Into<String>,
+        amount: Uint128,
+        min_duration: Duration,
+    ) -> StdResult<bool> {
+        let msg = QueryMsg::HasMinStake {
+            address: address.into(),
+            amount,
+            min_duration,
+        };
+        let res: HasMinStakeResponse = self.query_wasm_smart(contract, &msg)?;
+        Ok(res.qualifies)
+    }
+}
+
+/// Commitment for `ExecuteMsg::CommitUnstake`: sha256 of `"{amount}:{salt}"`.
+pub fn unstake_commitment(amount: Uint128, salt: &str) -> HexBinary {
+    HexBinary::from(Sha256::digest(format!("{amount}:{salt}")).as_slice())
+}
@@ -5,6 +5,7 @@ mod tests {
     use cosmwasm_std::testing::MockApi;
     use cosmwasm_std::{Addr, Coin, Empty, Uint128};
     use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
+    use cw_utils::Duration;
 
     pub fn contract_template() -> Box<dyn Contract<Empty>> {
         let contract = ContractWrapper::new(
@@ -17,7 +18,7 @@ mod tests {
 
     const USER: &str = "USER";
     const ADMIN: &str = "ADMIN";
-    const NATIVE_DENOM: &str = "denom";
+    const NATIVE_DENOM: &str = "token";
 
     fn mock_app() -> App {
         AppBuilder::new().build(|router, _, storage| {
@@ -45,7 +46,7 @@ mod tests {
             Uint128::new(1)
         );
 
-        let msg = InstantiateMsg { count: 1i32 };
+        let msg = InstantiateMsg { count: 1i32, initial_stakes: None, asset_metadata: None };
         let cw_template_contract_addr = app
             .instantiate_contract(
                 cw_template_id,
@@ -75,4 +76,592 @@ mod tests {
             app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
         }
     }
+
+    mod library {
+        use super::*;
+        use crate::contract::{execute as staking, init_state, query as staking_query};
+        use crate::msg::GetStakeResponse;
+        use crate::ContractError;
+        use cosmwasm_schema::cw_serde;
+        use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+
+        // a host contract embedding the staking handlers behind its own messages
+        #[cw_serde]
+        enum HostExecuteMsg {
+            Deposit { amount: Uint128 },
+        }
+
+        #[cw_serde]
+        enum HostQueryMsg {
+            Deposited { address: String },
+        }
+
+        fn host_instantiate(
+            deps: DepsMut,
+            _env: Env,
+            info: MessageInfo,
+            _msg: Empty,
+        ) -> Result<Response, ContractError> {
+            cw2::set_contract_version(deps.storage, "crates.io:host", "1.0.0")?;
+            init_state(deps.storage, info.sender, 0)?;
+            Ok(Response::new())
+        }
+
+        fn host_execute(
+            deps: DepsMut,
+            env: Env,
+            info: MessageInfo,
+            msg: HostExecuteMsg,
+        ) -> Result<Response, ContractError> {
+            match msg {
+                HostExecuteMsg::Deposit { amount } => staking::stake(deps, env, info, amount),
+            }
+        }
+
+        fn host_query(deps: Deps, _env: Env, msg: HostQueryMsg) -> StdResult<Binary> {
+            match msg {
+                HostQueryMsg::Deposited { address } => to_json_binary(&staking_query::stake(deps, address)?),
+            }
+        }
+
+        #[test]
+        fn host_contract_composes_staking_logic() {
+            let mut app = mock_app();
+            let code_id = app.store_code(Box::new(ContractWrapper::new(
+                host_execute,
+                host_instantiate,
+                host_query,
+            )));
+            let host = app
+                .instantiate_contract(code_id, Addr::unchecked(ADMIN), &Empty {}, &[], "host", None)
+                .unwrap();
+
+            let user = app.api().addr_make(USER);
+            let msg = HostExecuteMsg::Deposit { amount: Uint128::new(1) };
+            app.execute_contract(user.clone(), host.clone(), &msg, &[Coin::new(1u128, NATIVE_DENOM)])
+                .unwrap();
+
+            let res: GetStakeResponse = app
+                .wrap()
+                .query_wasm_smart(&host, &HostQueryMsg::Deposited { address: user.to_string() })
+                .unwrap();
+            assert_eq!(res.amount, Uint128::new(1));
+
+            let info = cw2::query_contract_info(&app.wrap(), &host).unwrap();
+            assert_eq!(info.contract, "crates.io:host");
+        }
+    }
+
+    mod evacuation {
+        use super::*;
+        use crate::msg::{ExecuteMsg, GetStakeResponse, QueryMsg};
+        use crate::ContractError;
+        use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult};
+        use cw_multi_test::error::AnyError;
+
+        const STAKE_DENOM: &str = "token";
+
+        // a recovery contract that simply holds whatever it is sent
+        fn escrow_template() -> Box<dyn Contract<Empty>> {
+            fn instantiate(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+                Ok(Response::new())
+            }
+            fn execute(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+                Ok(Response::new())
+            }
+            fn query(_: Deps, _: Env, _: Empty) -> StdResult<Binary> {
+                Err(StdError::generic_err("escrow has no queries"))
+            }
+            Box::new(ContractWrapper::new(execute, instantiate, query))
+        }
+
+        fn setup() -> (App, Addr, Addr, Addr) {
+            let api = MockApi::default();
+            let user = api.addr_make(USER);
+            let mut app = AppBuilder::new().build(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &user, vec![Coin::new(1_000u128, STAKE_DENOM)])
+                    .unwrap();
+            });
+            let pool_id = app.store_code(contract_template());
+            let escrow_id = app.store_code(escrow_template());
+            let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+            let pool = app
+                .instantiate_contract(pool_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+                .unwrap();
+            let escrow = app
+                .instantiate_contract(escrow_id, Addr::unchecked(ADMIN), &Empty {}, &[], "escrow", None)
+                .unwrap();
+
+            let msg = ExecuteMsg::Stake { amount: Uint128::new(600) };
+            app.execute_contract(user.clone(), pool.clone(), &msg, &[Coin::new(600u128, STAKE_DENOM)])
+                .unwrap();
+            (app, pool, escrow, user)
+        }
+
+        fn error(err: AnyError) -> ContractError {
+            err.downcast().unwrap()
+        }
+
+        #[test]
+        fn evacuation_requires_a_registered_escrow_contract() {
+            let (mut app, pool, _escrow, user) = setup();
+
+            let err = app
+                .execute_contract(Addr::unchecked(ADMIN), pool.clone(), &ExecuteMsg::EmergencyEvacuate {}, &[])
+                .unwrap_err();
+            assert_eq!(error(err), ContractError::NoEscrow {});
+
+            // plain accounts can't be registered as escrow
+            let msg = ExecuteMsg::SetEscrow { address: Some(user.to_string()) };
+            let err = app.execute_contract(Addr::unchecked(ADMIN), pool.clone(), &msg, &[]).unwrap_err();
+            assert_eq!(error(err), ContractError::InvalidEscrow { address: user.to_string() });
+        }
+
+        #[test]
+        fn only_the_owner_can_evacuate() {
+            let (mut app, pool, escrow, user) = setup();
+
+            let msg = ExecuteMsg::SetEscrow { address: Some(escrow.to_string()) };
+            let err = app.execute_contract(user.clone(), pool.clone(), &msg, &[]).unwrap_err();
+            assert_eq!(error(err), ContractError::Unauthorized {});
+            app.execute_contract(Addr::unchecked(ADMIN), pool.clone(), &msg, &[]).unwrap();
+
+            let err = app
+                .execute_contract(user, pool.clone(), &ExecuteMsg::EmergencyEvacuate {}, &[])
+                .unwrap_err();
+            assert_eq!(error(err), ContractError::Unauthorized {});
+            assert_eq!(app.wrap().query_balance(&pool, STAKE_DENOM).unwrap().amount, Uint128::new(600));
+        }
+
+        #[test]
+        fn evacuation_moves_funds_to_escrow_and_freezes_the_pool() {
+            let (mut app, pool, escrow, user) = setup();
+            let admin = Addr::unchecked(ADMIN);
+
+            let msg = ExecuteMsg::SetEscrow { address: Some(escrow.to_string()) };
+            app.execute_contract(admin.clone(), pool.clone(), &msg, &[]).unwrap();
+            app.execute_contract(admin.clone(), pool.clone(), &ExecuteMsg::EmergencyEvacuate {}, &[])
+                .unwrap();
+
+            assert_eq!(app.wrap().query_balance(&pool, STAKE_DENOM).unwrap().amount, Uint128::zero());
+            assert_eq!(app.wrap().query_balance(&escrow, STAKE_DENOM).unwrap().amount, Uint128::new(600));
+
+            // the ledger survives so the escrow can reconcile refunds
+            let msg = QueryMsg::Stake { address: user.to_string() };
+            let res: GetStakeResponse = app.wrap().query_wasm_smart(&pool, &msg).unwrap();
+            assert_eq!(res.amount, Uint128::new(600));
+
+            let msg = ExecuteMsg::Stake { amount: Uint128::new(10) };
+            let err = app
+                .execute_contract(user.clone(), pool.clone(), &msg, &[Coin::new(10u128, STAKE_DENOM)])
+                .unwrap_err();
+            assert_eq!(error(err), ContractError::Paused {});
+            let msg = ExecuteMsg::Unstake { amount: Uint128::new(10), min_receive: None, recipients: None };
+            let err = app.execute_contract(user, pool.clone(), &msg, &[]).unwrap_err();
+            assert_eq!(error(err), ContractError::Paused {});
+
+            // the freeze can't be lifted, retargeted or repeated
+            let msg = ExecuteMsg::SetPaused { paused: false };
+            let err = app.execute_contract(admin.clone(), pool.clone(), &msg, &[]).unwrap_err();
+            assert_eq!(error(err), ContractError::Evacuated {});
+            let msg = ExecuteMsg::SetEscrow { address: None };
+            let err = app.execute_contract(admin.clone(), pool.clone(), &msg, &[]).unwrap_err();
+            assert_eq!(error(err), ContractError::Evacuated {});
+            let err = app
+                .execute_contract(admin, pool, &ExecuteMsg::EmergencyEvacuate {}, &[])
+                .unwrap_err();
+            assert_eq!(error(err), ContractError::Evacuated {});
+        }
+    }
+
+    mod querier {
+        use super::*;
+        use crate::helpers::DadStakingQuerier;
+        use crate::msg::ExecuteMsg;
+        use cosmwasm_std::Decimal;
+
+        const STAKE_DENOM: &str = "token";
+
+        #[test]
+        fn extension_trait_queries_the_pool() {
+            let api = MockApi::default();
+            let user = api.addr_make(USER);
+            let mut app = AppBuilder::new().build(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &user, vec![Coin::new(1_000u128, STAKE_DENOM)])
+                    .unwrap();
+            });
+            let pool_id = app.store_code(contract_template());
+            let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+            let pool = app
+                .instantiate_contract(pool_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+                .unwrap();
+            let msg = ExecuteMsg::Stake { amount: Uint128::new(400) };
+            app.execute_contract(user.clone(), pool.clone(), &msg, &[Coin::new(400u128, STAKE_DENOM)])
+                .unwrap();
+
+            let querier = app.wrap();
+            assert_eq!(querier.dad_stake(&pool, &user).unwrap(), Uint128::new(400));
+            assert_eq!(querier.dad_stake(&pool, api.addr_make("nobody")).unwrap(), Uint128::zero());
+            assert_eq!(querier.dad_total_staked(&pool).unwrap(), Uint128::new(400));
+            assert_eq!(querier.dad_share_of_pool(&pool, &user).unwrap().share, Decimal::one());
+            assert!(querier.dad_has_min_stake(&pool, &user, Uint128::new(400), Duration::Time(0)).unwrap());
+            assert!(!querier.dad_has_min_stake(&pool, &user, Uint128::new(401), Duration::Time(0)).unwrap());
+        }
+    }
+
+    mod migration {
+        use super::*;
+        use crate::msg::{ConfigUpdate, ExecuteMsg, GetStakeResponse, QueryMsg};
+        use crate::ContractError;
+        use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+
+        const STAKE_DENOM: &str = "token";
+
+        // a contract that accepts anything sent to it but is not a pool
+        fn sink_template() -> Box<dyn Contract<Empty>> {
+            fn instantiate(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+                Ok(Response::new())
+            }
+            fn execute(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+                Ok(Response::new())
+            }
+            fn query(_: Deps, _: Env, _: Empty) -> StdResult<Binary> {
+                Ok(Binary::default())
+            }
+            Box::new(ContractWrapper::new(execute, instantiate, query))
+        }
+
+        fn setup() -> (App, Addr, Addr, Addr) {
+            let api = MockApi::default();
+            let user = api.addr_make(USER);
+            let mut app = AppBuilder::new().build(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &user, vec![Coin::new(1_000u128, STAKE_DENOM)])
+                    .unwrap();
+            });
+            let pool_id = app.store_code(contract_template());
+            let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+            let old_pool = app
+                .instantiate_contract(pool_id, Addr::unchecked(ADMIN), &msg, &[], "old pool", None)
+                .unwrap();
+            let new_pool = app
+                .instantiate_contract(pool_id, Addr::unchecked(ADMIN), &msg, &[], "new pool", None)
+                .unwrap();
+
+            let msg = ExecuteMsg::Stake { amount: Uint128::new(600) };
+            app.execute_contract(user.clone(), old_pool.clone(), &msg, &[Coin::new(600u128, STAKE_DENOM)])
+                .unwrap();
+            (app, old_pool, new_pool, user)
+        }
+
+        fn stake(app: &App, pool: &Addr, user: &Addr) -> Uint128 {
+            let msg = QueryMsg::Stake { address: user.to_string() };
+            let res: GetStakeResponse = app.wrap().query_wasm_smart(pool, &msg).unwrap();
+            res.amount
+        }
+
+        #[test]
+        fn stake_moves_to_the_target_pool_in_one_transaction() {
+            let (mut app, old_pool, new_pool, user) = setup();
+
+            let msg = ExecuteMsg::MigrateStake { target_pool: new_pool.to_string(), amount: Uint128::new(400) };
+            app.execute_contract(user.clone(), old_pool.clone(), &msg, &[]).unwrap();
+
+            assert_eq!(stake(&app, &old_pool, &user), Uint128::new(200));
+            assert_eq!(stake(&app, &new_pool, &user), Uint128::new(400));
+            assert_eq!(app.wrap().query_balance(&old_pool, STAKE_DENOM).unwrap().amount, Uint128::new(200));
+            assert_eq!(app.wrap().query_balance(&new_pool, STAKE_DENOM).unwrap().amount, Uint128::new(400));
+            assert_eq!(app.wrap().query_balance(&user, STAKE_DENOM).unwrap().amount, Uint128::new(400));
+        }
+
+        #[test]
+        fn target_must_be_another_pool_contract() {
+            let (mut app, old_pool, new_pool, user) = setup();
+            let sink_id = app.store_code(sink_template());
+            let sink = app
+                .instantiate_contract(sink_id, Addr::unchecked(ADMIN), &Empty {}, &[], "sink", None)
+                .unwrap();
+
+            for target in [old_pool.clone(), user.clone(), sink] {
+                let msg = ExecuteMsg::MigrateStake { target_pool: target.to_string(), amount: Uint128::new(100) };
+                let err = app.execute_contract(user.clone(), old_pool.clone(), &msg, &[]).unwrap_err();
+                assert_eq!(
+                    err.downcast::<ContractError>().unwrap(),
+                    ContractError::InvalidTargetPool { address: target.to_string() }
+                );
+            }
+
+            // a failed stake on the other side reverts the unstake as well
+            let msg = ExecuteMsg::SetPaused { paused: true };
+            app.execute_contract(Addr::unchecked(ADMIN), new_pool.clone(), &msg, &[]).unwrap();
+            let msg = ExecuteMsg::MigrateStake { target_pool: new_pool.to_string(), amount: Uint128::new(100) };
+            app.execute_contract(user.clone(), old_pool.clone(), &msg, &[]).unwrap_err();
+            assert_eq!(stake(&app, &old_pool, &user), Uint128::new(600));
+            assert_eq!(app.wrap().query_balance(&old_pool, STAKE_DENOM).unwrap().amount, Uint128::new(600));
+        }
+
+        #[test]
+        fn trusted_pools_take_locked_stake_right_away() {
+            let (mut app, old_pool, new_pool, user) = setup();
+            let admin = Addr::unchecked(ADMIN);
+            let update = ConfigUpdate { deposit_lock_blocks: Some(100), ..Default::default() };
+            let msg = ExecuteMsg::UpdateConfig(Box::new(update));
+            app.execute_contract(admin.clone(), old_pool.clone(), &msg, &[]).unwrap();
+            let msg = ExecuteMsg::Stake { amount: Uint128::new(300) };
+            app.execute_contract(user.clone(), old_pool.clone(), &msg, &[Coin::new(300u128, STAKE_DENOM)])
+                .unwrap();
+
+            let migrate = ExecuteMsg::MigrateStake { target_pool: new_pool.to_string(), amount: Uint128::new(900) };
+            let err = app.execute_contract(user.clone(), old_pool.clone(), &migrate, &[]).unwrap_err();
+            assert!(matches!(err.downcast::<ContractError>().unwrap(), ContractError::DepositLocked { .. }));
+
+            let msg = ExecuteMsg::AddTrustedPool { address: new_pool.to_string() };
+            app.execute_contract(admin, old_pool.clone(), &msg, &[]).unwrap();
+            app.execute_contract(user.clone(), old_pool.clone(), &migrate, &[]).unwrap();
+            assert_eq!(stake(&app, &old_pool, &user), Uint128::zero());
+            assert_eq!(stake(&app, &new_pool, &user), Uint128::new(900));
+        }
+    }
+
+    mod receipts {
+        use super::*;
+        use crate::msg::{ExecuteMsg, ReceiptCallback, StakeReceipt};
+        use crate::ContractError;
+        use cosmwasm_schema::cw_serde;
+        use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, WasmMsg};
+        use cw_storage_plus::Item;
+
+        const STAKE_DENOM: &str = "token";
+        const LAST_RECEIPT: Item<StakeReceipt> = Item::new("last_receipt");
+
+        #[cw_serde]
+        enum VaultMsg {
+            Deposit { pool: String },
+            StakeReceipt(StakeReceipt),
+        }
+
+        // a vault that stakes deposits for its users and books each position from the receipt
+        fn vault_template() -> Box<dyn Contract<Empty>> {
+            fn instantiate(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+                Ok(Response::new())
+            }
+            fn execute(deps: DepsMut, _: Env, info: MessageInfo, msg: VaultMsg) -> StdResult<Response> {
+                match msg {
+                    VaultMsg::Deposit { pool } => {
+                        let stake_for = ExecuteMsg::StakeFor {
+                            recipient: info.sender.to_string(),
+                            callback: None,
+                            receipt: Some(ReceiptCallback { msg: Some(Binary::from(b"deposit")) }),
+                        };
+                        Ok(Response::new().add_message(WasmMsg::Execute {
+                            contract_addr: pool,
+                            msg: to_json_binary(&stake_for)?,
+                            funds: info.funds,
+                        }))
+                    }
+                    VaultMsg::StakeReceipt(receipt) => {
+                        LAST_RECEIPT.save(deps.storage, &receipt)?;
+                        Ok(Response::new())
+                    }
+                }
+            }
+            fn query(deps: Deps, _: Env, _: Empty) -> StdResult<Binary> {
+                to_json_binary(&LAST_RECEIPT.load(deps.storage)?)
+            }
+            Box::new(ContractWrapper::new(execute, instantiate, query))
+        }
+
+        fn setup() -> (App, Addr, Addr, Addr) {
+            let api = MockApi::default();
+            let user = api.addr_make(USER);
+            let mut app = AppBuilder::new().build(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &user, vec![Coin::new(1_000u128, STAKE_DENOM)])
+                    .unwrap();
+            });
+            let pool_id = app.store_code(contract_template());
+            let vault_id = app.store_code(vault_template());
+            let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+            let pool = app
+                .instantiate_contract(pool_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+                .unwrap();
+            let vault = app
+                .instantiate_contract(vault_id, Addr::unchecked(ADMIN), &Empty {}, &[], "vault", None)
+                .unwrap();
+            (app, pool, vault, user)
+        }
+
+        #[test]
+        fn vault_books_the_receipt_in_the_same_transaction() {
+            let (mut app, pool, vault, user) = setup();
+
+            for (amount, new_balance) in [(300u128, 300u128), (200, 500)] {
+                let msg = VaultMsg::Deposit { pool: pool.to_string() };
+                app.execute_contract(user.clone(), vault.clone(), &msg, &[Coin::new(amount, STAKE_DENOM)])
+                    .unwrap();
+                let receipt: StakeReceipt = app.wrap().query_wasm_smart(&vault, &Empty {}).unwrap();
+                assert_eq!(
+                    receipt,
+                    StakeReceipt {
+                        pool: pool.to_string(),
+                        staker: user.to_string(),
+                        amount: Uint128::new(amount),
+                        denom: STAKE_DENOM.to_string(),
+                        new_balance: Uint128::new(new_balance),
+                        staked_since: app.block_info().time,
+                        msg: Some(Binary::from(b"deposit")),
+                    }
+                );
+            }
+        }
+
+        #[test]
+        fn only_contracts_can_ask_for_a_receipt() {
+            let (mut app, pool, _, user) = setup();
+
+            let msg = ExecuteMsg::StakeFor {
+                recipient: user.to_string(),
+                callback: None,
+                receipt: Some(ReceiptCallback { msg: None }),
+            };
+            let err = app
+                .execute_contract(user.clone(), pool, &msg, &[Coin::new(100u128, STAKE_DENOM)])
+                .unwrap_err();
+            assert_eq!(
+                err.downcast::<ContractError>().unwrap(),
+                ContractError::ReceiptNotContract { address: user.to_string() }
+            );
+        }
+    }
+
+    mod simulation {
+        use super::*;
+        use crate::fixtures::Lcg;
+        use crate::msg::{DailyStatsResponse, ExecuteMsg, GetStakeResponse, MetricsResponse, QueryMsg};
+        use std::collections::BTreeMap;
+
+        const STAKE_DENOM: &str = "token";
+        const STAKERS: [&str; 4] = ["alice", "bob", "carol", "dave"];
+        const INITIAL_BALANCE: u128 = 1_000_000;
+        const EPOCHS: u64 = 40;
+        const EPOCH_SECONDS: u64 = 86_400;
+
+        /// Independent bookkeeping of what the pool should hold after each action.
+        #[derive(Default)]
+        struct Model {
+            stakes: BTreeMap<String, u128>,
+            wallets: BTreeMap<String, u128>,
+            lifetime_staked: u128,
+            lifetime_unstaked: u128,
+            // (staked_in, unstaked_out) per epoch
+            epochs: Vec<(u128, u128)>,
+        }
+
+        #[test]
+        fn stake_and_unstake_over_many_epochs_match_reference_model() {
+            let api = MockApi::default();
+            let mut app = AppBuilder::new().build(|router, _, storage| {
+                for name in STAKERS {
+                    router
+                        .bank
+                        .init_balance(storage, &api.addr_make(name), vec![Coin::new(INITIAL_BALANCE, STAKE_DENOM)])
+                        .unwrap();
+                }
+            });
+            let code_id = app.store_code(contract_template());
+            let pool = app
+                .instantiate_contract(code_id, Addr::unchecked(ADMIN), &InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None }, &[], "pool", None)
+                .unwrap();
+
+            let mut model = Model::default();
+            for name in STAKERS {
+                model.wallets.insert(name.to_string(), INITIAL_BALANCE);
+            }
+            let mut rng = Lcg(42);
+            let first_day = app.block_info().time.seconds() / EPOCH_SECONDS;
+
+            for _ in 0..EPOCHS {
+                let mut flows = (0u128, 0u128);
+                for _ in 0..6 {
+                    let name = STAKERS[(rng.next() % STAKERS.len() as u64) as usize];
+                    let addr = app.api().addr_make(name);
+                    let staked = model.stakes.get(name).copied().unwrap_or_default();
+                    let wallet = model.wallets[name];
+
+                    if rng.next() % 3 < 2 && wallet > 0 {
+                        let amount = 1 + (rng.next() as u128) % wallet.min(50_000);
+                        let msg = ExecuteMsg::Stake { amount: Uint128::new(amount) };
+                        app.execute_contract(addr, pool.clone(), &msg, &[Coin::new(amount, STAKE_DENOM)])
+                            .unwrap();
+                        *model.stakes.entry(name.to_string()).or_default() += amount;
+                        *model.wallets.get_mut(name).unwrap() -= amount;
+                        model.lifetime_staked += amount;
+                        flows.0 += amount;
+                    } else if staked > 0 {
+                        let amount = 1 + (rng.next() as u128) % staked;
+                        let msg = ExecuteMsg::Unstake { amount: Uint128::new(amount), min_receive: None, recipients: None };
+                        app.execute_contract(addr, pool.clone(), &msg, &[]).unwrap();
+                        *model.stakes.get_mut(name).unwrap() -= amount;
+                        *model.wallets.get_mut(name).unwrap() += amount;
+                        model.lifetime_unstaked += amount;
+                        flows.1 += amount;
+                    }
+                }
+                model.epochs.push(flows);
+                app.update_block(|block| {
+                    block.height += 14_400;
+                    block.time = block.time.plus_seconds(EPOCH_SECONDS);
+                });
+            }
+
+            for name in STAKERS {
+                let addr = app.api().addr_make(name);
+                let res: GetStakeResponse = app
+                    .wrap()
+                    .query_wasm_smart(&pool, &QueryMsg::Stake { address: addr.to_string() })
+                    .unwrap();
+                assert_eq!(res.amount.u128(), model.stakes.get(name).copied().unwrap_or_default());
+                let wallet = app.wrap().query_balance(&addr, STAKE_DENOM).unwrap().amount;
+                assert_eq!(wallet.u128(), model.wallets[name]);
+            }
+
+            let total: u128 = model.stakes.values().sum();
+            let pool_balance = app.wrap().query_balance(&pool, STAKE_DENOM).unwrap().amount;
+            assert_eq!(pool_balance.u128(), total);
+
+            let metrics: MetricsResponse = app.wrap().query_wasm_smart(&pool, &QueryMsg::Metrics {}).unwrap();
+            assert_eq!(metrics.total_staked.u128(), total);
+            assert_eq!(metrics.lifetime_staked.u128(), model.lifetime_staked);
+            assert_eq!(metrics.lifetime_unstaked.u128(), model.lifetime_unstaked);
+
+            // every epoch is one day, so the daily series must line up with the modelled flows
+            let mut start_day = first_day;
+            let mut days = vec![];
+            loop {
+                let msg = QueryMsg::DailyStats { start_day: Some(start_day), end_day: None, limit: None };
+                let page: DailyStatsResponse = app.wrap().query_wasm_smart(&pool, &msg).unwrap();
+                match page.days.last() {
+                    Some(last) => start_day = last.day + 1,
+                    None => break,
+                }
+                days.extend(page.days);
+            }
+            for (day, (staked_in, unstaked_out)) in model.epochs.iter().enumerate() {
+                let recorded = days.iter().find(|entry| entry.day == first_day + day as u64);
+                match recorded {
+                    Some(entry) => {
+                        assert_eq!(entry.staked_in.u128(), *staked_in);
+                        assert_eq!(entry.unstaked_out.u128(), *unstaked_out);
+                    }
+                    None => assert_eq!((*staked_in, *unstaked_out), (0, 0)),
+                }
+            }
+        }
+    }
 }
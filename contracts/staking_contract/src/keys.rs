@@ -0,0 +1,103 @@
+//! Storage namespaces of every `Item` and `Map` in [`crate::state`].
+//!
+//! Each namespace must be listed in [`ALL`] so the collision test covers it. Map entries are
+//! stored under a length-prefixed namespace, so distinct names can't overlap; two items or maps
+//! sharing one name would silently overwrite each other.
+
+pub const STATE: &str = "state";
+pub const CONFIG: &str = "config";
+pub const PAUSED: &str = "paused";
+pub const TVL_WINDOW: &str = "tvl_window";
+pub const EVACUATION: &str = "evacuation";
+pub const STAKES: &str = "stakes";
+pub const STAKE_TRANCHES: &str = "stake_tranches";
+pub const TOTAL_STAKED: &str = "total_staked";
+pub const LIFETIME_TOTALS: &str = "lifetime_totals";
+pub const DISTRIBUTION: &str = "distribution";
+pub const DAILY_STATS: &str = "daily_stats";
+pub const DAILY_ACTIVE: &str = "daily_active";
+pub const PROFILES: &str = "profiles";
+pub const CLAWBACKS: &str = "clawbacks";
+pub const UNSTAKE_ORDERS: &str = "unstake_orders";
+pub const NEXT_ORDER_ID: &str = "next_order_id";
+pub const REBATE_POOL: &str = "rebate_pool";
+pub const REBATE_ACCOUNTS: &str = "rebate_accounts";
+pub const FAUCET_CLAIMS: &str = "faucet_claims";
+pub const USER_STATS: &str = "user_stats";
+pub const UNSTAKE_COMMITS: &str = "unstake_commits";
+pub const RECENT_DEPOSITS: &str = "recent_deposits";
+pub const SIGNING_KEYS: &str = "signing_keys";
+pub const NONCES: &str = "nonces";
+pub const REBUILD: &str = "rebuild";
+pub const GUARDIAN: &str = "guardian";
+pub const OWNER_HEARTBEAT: &str = "owner_heartbeat";
+pub const UNSTAKE_CONFIRMATIONS: &str = "unstake_confirmations";
+pub const PENDING_UNSTAKES: &str = "pending_unstakes";
+pub const TRUSTED_POOLS: &str = "trusted_pools";
+pub const LEDGER: &str = "ledger";
+pub const STAKER_HASH_SALT: &str = "staker_hash_salt";
+pub const STAKER_HASHES: &str = "staker_hashes";
+pub const QUEUE_DEPTH: &str = "queue_depth";
+
+/// Namespace cw2 stores the contract name and version under
+pub const CW2_CONTRACT_INFO: &str = "contract_info";
+
+pub const ALL: &[&str] = &[
+    STATE,
+    CONFIG,
+    PAUSED,
+    TVL_WINDOW,
+    EVACUATION,
+    STAKES,
+    STAKE_TRANCHES,
+    TOTAL_STAKED,
+    LIFETIME_TOTALS,
+    DISTRIBUTION,
+    DAILY_STATS,
+    DAILY_ACTIVE,
+    PROFILES,
+    CLAWBACKS,
+    UNSTAKE_ORDERS,
+    NEXT_ORDER_ID,
+    REBATE_POOL,
+    REBATE_ACCOUNTS,
+    FAUCET_CLAIMS,
+    USER_STATS,
+    UNSTAKE_COMMITS,
+    RECENT_DEPOSITS,
+    SIGNING_KEYS,
+    NONCES,
+    REBUILD,
+    GUARDIAN,
+    OWNER_HEARTBEAT,
+    UNSTAKE_CONFIRMATIONS,
+    PENDING_UNSTAKES,
+    TRUSTED_POOLS,
+    LEDGER,
+    STAKER_HASH_SALT,
+    STAKER_HASHES,
+    QUEUE_DEPTH,
+    CW2_CONTRACT_INFO,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn namespaces_are_unique() {
+        let mut seen = BTreeSet::new();
+        for key in ALL {
+            assert!(!key.is_empty());
+            assert!(seen.insert(*key), "storage namespace {key:?} is used twice");
+        }
+    }
+
+    #[test]
+    fn cw2_namespace_matches_upstream() {
+        let mut storage = cosmwasm_std::testing::MockStorage::new();
+        cw2::set_contract_version(&mut storage, "name", "1.0.0").unwrap();
+        assert!(cosmwasm_std::Storage::get(&storage, CW2_CONTRACT_INFO.as_bytes()).is_some());
+    }
+}
@@ -1,8 +1,32 @@
+//! Native-token staking pool for CosmWasm.
+//!
+//! Built normally, the crate exports the `instantiate`, `execute`, `query` and `migrate` entry
+//! points. With the `library` feature the entry points are not exported and another contract can
+//! compose the staking logic in-process instead:
+//!
+//! - call [`contract::init_state`] from the host's own `instantiate` (it leaves the host's cw2
+//!   contract info alone),
+//! - route the host's messages to the handlers in [`contract::execute`] and [`contract::query`],
+//!   which take the same `Deps`/`DepsMut`, `Env` and `MessageInfo` the host received,
+//! - read state directly through the storage items in [`state`].
+//!
+//! The staking state lives in the host's storage under the namespaces listed in [`keys`], so the
+//! host must not use any of them itself.
+
 pub mod contract;
 mod error;
+#[cfg(test)]
+mod fixtures;
+#[cfg(test)]
+mod gas_bench;
+#[cfg(test)]
+mod golden;
 pub mod helpers;
 pub mod integration_tests;
+pub mod keys;
+mod migrations;
 pub mod msg;
+pub mod signing;
 pub mod state;
 
 pub use crate::error::ContractError;
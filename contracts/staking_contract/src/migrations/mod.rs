@@ -0,0 +1,37 @@
+use cosmwasm_std::{Env, Storage};
+use semver::Version;
+
+use crate::error::ContractError;
+
+mod v0_1_to_v0_2;
+
+/// Applies every storage transform between the stored and the current minor version, in order.
+pub fn run(
+    storage: &mut dyn Storage,
+    env: &Env,
+    from: &Version,
+    to: &Version,
+) -> Result<(), ContractError> {
+    if from > to {
+        return Err(ContractError::MigrationDowngrade {
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+    }
+
+    let mut current = (from.major, from.minor);
+    while current < (to.major, to.minor) {
+        current = match current {
+            (0, 1) => {
+                v0_1_to_v0_2::migrate(storage, env)?;
+                (0, 2)
+            }
+            (major, minor) => {
+                return Err(ContractError::UnsupportedMigration {
+                    from: format!("{}.{}", major, minor),
+                })
+            }
+        };
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use cosmwasm_std::{Env, Int256, Order, StdResult, Storage, Uint128};
+
+use crate::contract::update_distribution;
+use crate::error::ContractError;
+use crate::state::{
+    Config, Ledger, LifetimeTotals, StakeTranche, CONFIG, LEDGER, LIFETIME_TOTALS, PAUSED,
+    STAKES, STAKE_TRANCHES, TOTAL_STAKED,
+};
+
+/// v0.1 only stored per-address stakes; v0.2 adds the pool total, the stake size distribution,
+/// the paused flag and the config.
+/// Daily statistics start recording from the upgrade onwards, and lifetime totals start from the
+/// current pool total since earlier flows weren't recorded. The ledger opens with the pool total
+/// held in cash and owed as principal. v0.1 kept no stake ages, so every existing stake counts
+/// as held since the upgrade.
+pub fn migrate(storage: &mut dyn Storage, env: &Env) -> Result<(), ContractError> {
+    let stakes = STAKES
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut total = Uint128::zero();
+    for (staker, amount) in stakes {
+        let tranche = StakeTranche {
+            amount,
+            height: env.block.height,
+            time: env.block.time,
+        };
+        STAKE_TRANCHES.save(storage, &staker, &vec![tranche])?;
+        total = total
+            .checked_add(amount)
+            .map_err(|_| ContractError::Overflow { ledger: "total_staked".to_string() })?;
+        update_distribution(storage, Uint128::zero(), amount)?;
+    }
+    TOTAL_STAKED.save(storage, &total)?;
+    LIFETIME_TOTALS.save(
+        storage,
+        &LifetimeTotals {
+            staked: total,
+            ..LifetimeTotals::default()
+        },
+    )?;
+    let opening = Int256::from(total);
+    LEDGER.save(
+        storage,
+        &Ledger {
+            cash: opening,
+            principal: -opening,
+            ..Ledger::default()
+        },
+    )?;
+    PAUSED.save(storage, &false)?;
+    CONFIG.save(storage, &Config::default())?;
+    Ok(())
+}
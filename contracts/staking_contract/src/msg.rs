@@ -1,30 +1,430 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use serde::{Serialize, Deserialize};
-use schemars::JsonSchema;
-use cosmwasm_std::{Uint128};
+use cosmwasm_std::{
+    to_json_binary, Binary, Coin, CosmosMsg, Decimal, HexBinary, Int256, StdResult, Timestamp,
+    Uint128, WasmMsg,
+};
+
+use cw_utils::{Duration, Expiration};
+
+use crate::state::{
+    AssetMetadata, CommitReveal, Config, ExitFeeTier, FeeDiscountTier, Guardian, Heartbeat,
+    LedgerAccount, OrderCondition, PoolMetadata, RebateRates, RebuildKind, TvlAlert,
+};
 
 #[cw_serde]
 pub struct InstantiateMsg {
     pub count: i32,
+    /// Genesis stakes, funded by the staking-denom coins attached to the instantiation
+    pub initial_stakes: Option<Vec<(String, Uint128)>>,
+    /// Display metadata for wallets; falls back to the chain's denom metadata when omitted
+    pub asset_metadata: Option<AssetMetadata>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[cw_serde]
+pub struct MigrateMsg {}
+
+/// Variants used to serialize as PascalCase; the aliases keep those payloads working.
+#[cw_serde]
 pub enum ExecuteMsg {
+    #[serde(alias = "Increment")]
     Increment {},
+    #[serde(alias = "Reset")]
     Reset { count: i32 },
+    #[serde(alias = "Stake")]
     Stake { amount: Uint128 },
-    Unstake { amount: Uint128 }
+    /// `min_receive` aborts the unstake if the payout after exit fees would fall below it.
+    /// `recipients` splits the payout by shares that add up to one; the staker gets it all
+    /// when unset.
+    #[serde(alias = "Unstake")]
+    Unstake {
+        amount: Uint128,
+        min_receive: Option<Uint128>,
+        recipients: Option<Vec<(String, Decimal)>>,
+    },
+    /// First step of an unstake above the commit-reveal threshold: `hash` is
+    /// [`crate::helpers::unstake_commitment`] of the amount and a secret salt
+    #[serde(alias = "CommitUnstake")]
+    CommitUnstake { hash: HexBinary },
+    /// Unstakes a committed amount once the reveal delay has passed
+    #[serde(alias = "RevealUnstake")]
+    RevealUnstake {
+        amount: Uint128,
+        salt: String,
+        min_receive: Option<Uint128>,
+    },
+    /// Opt-in for contract stakers: later unstakes by the sender wait `blocks` blocks and pay out
+    /// only on `ConfirmUnstake`, leaving time to abort a compromised withdrawal. Zero turns it
+    /// off; lowering the delay takes effect once the current delay has passed. At most 432,000
+    /// blocks.
+    #[serde(alias = "SetUnstakeConfirmation")]
+    SetUnstakeConfirmation { blocks: u64 },
+    #[serde(alias = "ConfirmUnstake")]
+    ConfirmUnstake {},
+    #[serde(alias = "AbortUnstake")]
+    AbortUnstake {},
+    /// Unstakes `amount` under the usual exit rules and stakes the payout for the sender in
+    /// `target_pool`, another pool of this contract, in the same transaction. Trusted pools
+    /// take the stake over right away, regardless of deposit locks and the commit-reveal
+    /// threshold; the exit fee still applies.
+    #[serde(alias = "MigrateStake")]
+    MigrateStake { target_pool: String, amount: Uint128 },
+    /// Stakes the attached funds for `recipient`, optionally confirming the resulting balance
+    /// to a callback contract in the same transaction
+    #[serde(alias = "StakeFor")]
+    StakeFor {
+        recipient: String,
+        callback: Option<StakeCallback>,
+        /// Send a [`StakeReceipt`] back to the calling contract, so a vault can book the position
+        /// without querying the pool in a later block
+        receipt: Option<ReceiptCallback>,
+    },
+    #[serde(alias = "BatchStakeFor")]
+    BatchStakeFor { recipients: Vec<(String, Uint128)> },
+    #[serde(alias = "ImportStakes")]
+    ImportStakes {
+        stakes: Vec<(String, Uint128)>,
+        confirm_within: Duration,
+    },
+    #[serde(alias = "ConfirmImport")]
+    ConfirmImport {},
+    #[serde(alias = "ClawbackImport")]
+    ClawbackImport { address: String },
+    #[serde(alias = "CheckInvariants")]
+    CheckInvariants {},
+    /// Recomputes an aggregate from the stakes map, scanning up to `limit` stakers per call, to
+    /// repair drift. Only while paused; the stored aggregate is replaced once the scan completes.
+    /// `staker_hashes` builds the `HashedStakes` index under a new salt and turns the listing on.
+    #[serde(alias = "Rebuild")]
+    Rebuild { kind: RebuildKind, limit: Option<u32> },
+    #[serde(alias = "SetPaused")]
+    SetPaused { paused: bool },
+    #[serde(alias = "SetProfile")]
+    SetProfile {
+        display_name: Option<String>,
+        url: Option<String>,
+        avatar_hash: Option<String>,
+    },
+    /// Registers, replaces or, with `None`, removes the sender's key for off-chain signed messages
+    #[serde(alias = "SetSigningKey")]
+    SetSigningKey { pubkey: Option<Binary> },
+    /// Cancels every outstanding message signed with the sender's current nonce
+    #[serde(alias = "InvalidateNonce")]
+    InvalidateNonce {},
+    /// Changes any number of settings in one go; fields left out keep their value. Optional
+    /// settings are cleared through their dedicated message.
+    #[serde(alias = "UpdateConfig")]
+    UpdateConfig(Box<ConfigUpdate>),
+    #[serde(alias = "SetFeeDiscountTiers")]
+    SetFeeDiscountTiers { tiers: Vec<FeeDiscountTier> },
+    /// Piecewise exit fee: a withdrawal pays the rate of the highest tier its share of the total
+    /// staked reaches. Every fee must stay below 100%, and goes to the owner.
+    #[serde(alias = "SetExitFeeTiers")]
+    SetExitFeeTiers { tiers: Vec<ExitFeeTier> },
+    #[serde(alias = "SetPoolMetadata")]
+    SetPoolMetadata { metadata: Option<PoolMetadata> },
+    #[serde(alias = "SetRejectContractStakers")]
+    SetRejectContractStakers { reject: bool },
+    #[serde(alias = "SetTvlAlert")]
+    SetTvlAlert { alert: Option<TvlAlert> },
+    #[serde(alias = "SetEscrow")]
+    SetEscrow { address: Option<String> },
+    /// Names or, with `None`, removes the backup admin for a lost owner key. The inactivity
+    /// period may be at most about ten years
+    #[serde(alias = "SetGuardian")]
+    SetGuardian { guardian: Option<NewGuardian> },
+    /// Lets the guardian become the owner once the owner has made no admin call for the
+    /// guardian's inactivity period
+    #[serde(alias = "ClaimOwnership")]
+    ClaimOwnership {},
+    /// Registers a sibling pool, e.g. an upgraded deployment, as a trusted migration target
+    #[serde(alias = "AddTrustedPool")]
+    AddTrustedPool { address: String },
+    #[serde(alias = "RemoveTrustedPool")]
+    RemoveTrustedPool { address: String },
+    #[serde(alias = "SetCommitReveal")]
+    SetCommitReveal { rule: Option<CommitReveal> },
+    /// Locks deposits for `blocks` blocks, so funds can't be staked and withdrawn in the same
+    /// block (e.g. with a flash loan) to game stake-weighted queries; zero turns it off. At most
+    /// 432,000 blocks.
+    #[serde(alias = "SetDepositLockBlocks")]
+    SetDepositLockBlocks { blocks: u64 },
+    #[serde(alias = "SetRecoveryMode")]
+    SetRecoveryMode { enabled: bool },
+    /// Sets the deadline for new stakes, or extends it before it passes. A deadline can't be
+    /// brought forward or lifted.
+    #[serde(alias = "SetDepositsCloseAt")]
+    SetDepositsCloseAt { close_at: Expiration },
+    #[serde(alias = "ScheduleUnstake")]
+    ScheduleUnstake {
+        amount: Uint128,
+        execute_at: Expiration,
+        bounty: Option<Uint128>,
+        condition: Option<OrderCondition>,
+    },
+    #[serde(alias = "ExecuteUnstakeOrder")]
+    ExecuteUnstakeOrder { id: u64 },
+    #[serde(alias = "CancelUnstakeOrder")]
+    CancelUnstakeOrder { id: u64 },
+    #[serde(alias = "SetRebateRates")]
+    SetRebateRates { rates: Option<RebateRates> },
+    #[serde(alias = "FundRebates")]
+    FundRebates {},
+    /// `recipients` splits the claim as in `Unstake`
+    #[serde(alias = "ClaimRebates")]
+    ClaimRebates { recipients: Option<Vec<(String, Decimal)>> },
+    #[cfg(feature = "testnet")]
+    #[serde(alias = "Faucet")]
+    Faucet {},
+    #[serde(alias = "EmergencyEvacuate")]
+    EmergencyEvacuate {},
+}
+
+impl ExecuteMsg {
+    /// Whether the message deposits funds; every other message must be sent without any.
+    pub fn accepts_funds(&self) -> bool {
+        matches!(
+            self,
+            ExecuteMsg::Stake { .. }
+                | ExecuteMsg::StakeFor { .. }
+                | ExecuteMsg::BatchStakeFor { .. }
+                | ExecuteMsg::ImportStakes { .. }
+                | ExecuteMsg::FundRebates {}
+        )
+    }
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct ConfigUpdate {
+    pub fee_discount_tiers: Option<Vec<FeeDiscountTier>>,
+    pub exit_fee_tiers: Option<Vec<ExitFeeTier>>,
+    pub reject_contract_stakers: Option<bool>,
+    pub asset_metadata: Option<AssetMetadata>,
+    pub tvl_alert: Option<TvlAlert>,
+    pub escrow: Option<String>,
+    pub recovery_mode: Option<bool>,
+    pub rebate_rates: Option<RebateRates>,
+    /// Subject to the same rule as `SetDepositsCloseAt`: it can only be extended
+    pub deposits_close_at: Option<Expiration>,
+    pub commit_reveal: Option<CommitReveal>,
+    pub deposit_lock_blocks: Option<u64>,
+    pub pool_metadata: Option<PoolMetadata>,
+    pub liquidity_check: Option<bool>,
+    /// Can only turn the listing off; `Rebuild { kind: staker_hashes }` turns it on
+    pub hashed_enumeration: Option<bool>,
+}
+
+#[cw_serde]
+pub struct NewGuardian {
+    pub address: String,
+    pub inactivity: Duration,
+}
+
+#[cw_serde]
+pub struct StakeCallback {
+    pub contract: String,
+    /// Opaque payload passed back to the callee untouched
+    pub msg: Option<Binary>,
+}
+
+#[cw_serde]
+pub struct ReceiptCallback {
+    /// Opaque payload passed back to the caller untouched
+    pub msg: Option<Binary>,
+}
+
+/// Execute message the pool sends to a `StakeFor` callback contract, which must accept
+/// `{"after_stake": {...}}` in its own ExecuteMsg, or to a caller that asked for a receipt,
+/// which must accept `{"stake_receipt": {...}}`
+#[cw_serde]
+pub enum StakeCallbackMsg {
+    AfterStake(AfterStakeMsg),
+    StakeReceipt(StakeReceipt),
+}
+
+#[cw_serde]
+pub struct AfterStakeMsg {
+    pub staker: String,
+    pub amount: Uint128,
+    pub new_balance: Uint128,
+    pub msg: Option<Binary>,
+}
+
+impl AfterStakeMsg {
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg: to_json_binary(&StakeCallbackMsg::AfterStake(self))?,
+            funds: vec![],
+        }
+        .into())
+    }
+}
+
+#[cw_serde]
+pub struct StakeReceipt {
+    /// Each staker holds a single position per pool, so `pool` and `staker` identify it
+    pub pool: String,
+    pub staker: String,
+    pub amount: Uint128,
+    pub denom: String,
+    pub new_balance: Uint128,
+    /// When the oldest part of the position was added, as aged by `HasMinStake`. The pool pays
+    /// no rewards, so this is the only checkpoint a position has.
+    pub staked_since: Timestamp,
+    pub msg: Option<Binary>,
+}
+
+impl StakeReceipt {
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg: to_json_binary(&StakeCallbackMsg::StakeReceipt(self))?,
+            funds: vec![],
+        }
+        .into())
+    }
 }
 
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
-    // GetCount returns the current count as a json-encoded number
+    // Count returns the current count as a json-encoded number
     #[returns(GetCountResponse)]
-    GetCount {},
+    #[serde(alias = "get_count")]
+    Count {},
 
     #[returns(GetStakeResponse)]
-    GetStake { address: String },
+    #[serde(alias = "get_stake")]
+    Stake { address: String },
+
+    // Stakes returns the stakes of up to 30 addresses, in the order given
+    #[returns(GetStakesResponse)]
+    #[serde(alias = "get_stakes")]
+    Stakes { addresses: Vec<String> },
+
+    // ShareOfPool returns the address' stake as a fraction of the total staked
+    #[returns(ShareOfPoolResponse)]
+    ShareOfPool { address: String },
+
+    // Config returns every owner-tunable setting, including the pool's listing metadata
+    #[returns(Config)]
+    #[serde(alias = "get_config")]
+    Config {},
+
+    // AssetInfo returns display metadata of the staked asset
+    #[returns(AssetInfoResponse)]
+    AssetInfo {},
+
+    // Distribution returns how many stakers hold a balance in each power-of-ten bucket
+    #[returns(DistributionResponse)]
+    Distribution {},
+
+    // Metrics returns the pool total and cumulative lifetime flows, including exit fees collected
+    #[returns(MetricsResponse)]
+    Metrics {},
+
+    // DailyStats returns the per-day aggregates between two day numbers (unix time / 86400), inclusive,
+    // at most `limit` days per page (capped at 30)
+    #[returns(DailyStatsResponse)]
+    DailyStats {
+        start_day: Option<u64>,
+        end_day: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    // FeeDiscount returns the fee discount an address' stake qualifies for, for DEX/marketplace integrations
+    #[returns(FeeDiscountResponse)]
+    FeeDiscount { address: String },
+
+    // HasMinStake tells whether an address has held at least `amount` for at least `min_duration`,
+    // in blocks or seconds. Stake added later ages on its own, and unstakes take the newest first
+    #[returns(HasMinStakeResponse)]
+    HasMinStake {
+        address: String,
+        amount: Uint128,
+        min_duration: Duration,
+    },
+
+    // PendingImports lists imported stakes that are still clawback-able, ordered by address
+    #[returns(PendingImportsResponse)]
+    PendingImports {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    // UnstakeOrders lists scheduled and conditional unstakes ordered by id, for keepers to pick up
+    #[returns(UnstakeOrdersResponse)]
+    UnstakeOrders {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    // Violations scans up to `limit` stakers after `start_after`, in address order, and lists the
+    // contracts among them while contract stakers are rejected; resume from `last_scanned`. While hashed
+    // enumeration is on, stakers are scanned in hash order and the cursor is a hex staker hash
+    // instead, so paging doesn't reveal the address order.
+    #[returns(ViolationsResponse)]
+    Violations {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    // Profiles lists staker profiles ordered by address
+    #[returns(ProfilesResponse)]
+    Profiles {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // TrustedPools lists the pools stake can be migrated to right away, ordered by address
+    #[returns(TrustedPoolsResponse)]
+    TrustedPools {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // HashedStakes lists every stake in hash order, keyed by a salted hash of the staker's address
+    // instead of the address, if the pool has opted in; resume from `last_scanned`. The hashes are
+    // pseudonymous ids, not private ones: the salt and the index behind them sit in contract
+    // storage, which anyone can read with a raw query
+    #[returns(HashedStakesResponse)]
+    HashedStakes {
+        start_after: Option<HexBinary>,
+        limit: Option<u32>,
+    },
+    // Guardian returns the backup admin, if any, and from when it may claim ownership
+    #[returns(GuardianResponse)]
+    Guardian {},
+
+    // Health summarises pause state, contract version, the balance invariant and the unstake
+    // queue backlog for monitoring
+    #[returns(HealthResponse)]
+    Health {},
+
+    // TrialBalance returns every ledger account's balance, debits positive, and how far the ledger
+    // has drifted from the recorded stakes and the contract's actual balance
+    #[returns(TrialBalanceResponse)]
+    TrialBalance {},
+
+    // Rebates returns an address's counted actions and claimable gas rebates
+    #[returns(RebatesResponse)]
+    Rebates { address: String },
+
+    // UserStats returns an address's lifetime interaction counters and first/last interaction time
+    #[returns(UserStatsResponse)]
+    UserStats { address: String },
+
+    // Portfolio consolidates an address's positions, claimable rewards and anything holding back
+    // its exits, keyed by denom, in one response for frontends
+    #[returns(PortfolioResponse)]
+    Portfolio { address: String },
+
+    // Nonce returns the nonce an address's next signed message must carry, and its signing key
+    #[returns(NonceResponse)]
+    Nonce { address: String },
+
+    // Aggregate runs up to 10 non-nested sub-queries and returns their results in order
+    #[returns(AggregateResponse)]
+    Aggregate { queries: Vec<QueryMsg> },
 }
 
 // We define a custom struct for each query response
@@ -37,3 +437,303 @@ pub struct GetCountResponse {
 pub struct GetStakeResponse {
     pub amount: Uint128,
 }
+
+#[cw_serde]
+pub struct StakeEntry {
+    pub address: String,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct GetStakesResponse {
+    pub stakes: Vec<StakeEntry>,
+}
+
+#[cw_serde]
+pub struct ShareOfPoolResponse {
+    pub stake: Uint128,
+    pub total_staked: Uint128,
+    pub share: Decimal,
+}
+
+/// A stored record that no longer deserializes, e.g. after a partially applied migration. List
+/// queries skip such records and report them here, omitting the field when there are none, so
+/// the rest of the state stays readable during an incident.
+#[cw_serde]
+pub struct MalformedRecord {
+    /// Key of the record within the list's storage namespace
+    pub key: HexBinary,
+    pub error: String,
+}
+
+#[cw_serde]
+pub struct DistributionBucket {
+    /// Smallest balance counted in this bucket; the bucket ends below ten times this amount
+    pub min_amount: Uint128,
+    pub stakers: u64,
+}
+
+#[cw_serde]
+pub struct DistributionResponse {
+    pub buckets: Vec<DistributionBucket>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub malformed: Vec<MalformedRecord>,
+}
+
+#[cw_serde]
+pub struct DailyStatsEntry {
+    pub day: u64,
+    pub staked_in: Uint128,
+    pub unstaked_out: Uint128,
+    pub active_addresses: u64,
+}
+
+#[cw_serde]
+pub struct DailyStatsResponse {
+    pub days: Vec<DailyStatsEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub malformed: Vec<MalformedRecord>,
+}
+
+#[cw_serde]
+pub struct ProfileEntry {
+    pub address: String,
+    pub display_name: Option<String>,
+    pub url: Option<String>,
+    pub avatar_hash: Option<String>,
+}
+
+#[cw_serde]
+pub struct ProfilesResponse {
+    pub profiles: Vec<ProfileEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub malformed: Vec<MalformedRecord>,
+}
+
+#[cw_serde]
+pub struct FeeDiscountResponse {
+    pub stake: Uint128,
+    pub discount: Decimal,
+}
+
+#[cw_serde]
+pub struct HasMinStakeResponse {
+    pub qualifies: bool,
+    pub stake: Uint128,
+    /// When the oldest part of the stake was added, if the address has a stake
+    pub staked_since: Option<Timestamp>,
+}
+
+#[cw_serde]
+pub struct MetricsResponse {
+    pub total_staked: Uint128,
+    pub lifetime_staked: Uint128,
+    pub lifetime_unstaked: Uint128,
+    pub lifetime_fees: Uint128,
+}
+
+#[cw_serde]
+pub struct PendingImport {
+    pub address: String,
+    pub amount: Uint128,
+    pub deadline: Expiration,
+}
+
+#[cw_serde]
+pub struct PendingImportsResponse {
+    pub imports: Vec<PendingImport>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub malformed: Vec<MalformedRecord>,
+}
+
+#[cw_serde]
+pub struct AssetInfoResponse {
+    pub denom: String,
+    pub symbol: Option<String>,
+    pub decimals: Option<u32>,
+    pub logo_uri: Option<String>,
+}
+
+#[cw_serde]
+pub struct AggregateResponse {
+    pub results: Vec<Binary>,
+}
+
+#[cw_serde]
+pub struct TrustedPoolEntry {
+    pub address: String,
+    pub added_at_height: u64,
+}
+
+#[cw_serde]
+pub struct TrustedPoolsResponse {
+    pub pools: Vec<TrustedPoolEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub malformed: Vec<MalformedRecord>,
+}
+
+#[cw_serde]
+pub struct LedgerBalance {
+    pub account: LedgerAccount,
+    pub balance: Int256,
+}
+
+#[cw_serde]
+pub struct TrialBalanceResponse {
+    pub accounts: Vec<LedgerBalance>,
+    /// Principal the ledger owes minus `TOTAL_STAKED`; anything but zero means a stake changed
+    /// without a posting
+    pub principal_difference: Int256,
+    /// Cash the ledger holds minus the contract's bank balance of the staking denom; negative
+    /// when funds arrived outside any recorded flow, positive when recorded funds are missing
+    pub cash_difference: Int256,
+}
+
+#[cw_serde]
+pub struct GuardianResponse {
+    pub guardian: Option<Guardian>,
+    pub last_owner_action: Option<Heartbeat>,
+    /// Earliest time or height of a claim, unless the owner acts before then
+    pub claimable_at: Option<Expiration>,
+}
+
+#[cw_serde]
+pub struct HealthResponse {
+    pub paused: bool,
+    pub contract_version: String,
+    pub invariants_ok: bool,
+    pub total_staked: Uint128,
+    pub balance: Uint128,
+    /// Scheduled unstake orders not yet executed or cancelled
+    pub unstake_orders: u64,
+    /// Unstakes waiting for their staker's confirmation
+    pub pending_unstakes: u64,
+}
+
+#[cw_serde]
+pub struct UnstakeOrderEntry {
+    pub id: u64,
+    pub staker: String,
+    pub amount: Uint128,
+    pub execute_at: Expiration,
+    pub bounty: Uint128,
+    pub condition: Option<OrderCondition>,
+}
+
+#[cw_serde]
+pub struct UnstakeOrdersResponse {
+    pub orders: Vec<UnstakeOrderEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub malformed: Vec<MalformedRecord>,
+}
+
+#[cw_serde]
+pub struct RebatesResponse {
+    pub stakes: u64,
+    pub unstakes: u64,
+    pub claimable: Uint128,
+    /// Funds left in the rebate pool for future actions
+    pub pool: Uint128,
+}
+
+#[cw_serde]
+pub struct UserStatsResponse {
+    pub stakes: u64,
+    pub unstakes: u64,
+    pub claims: u64,
+    /// Unset until the address first interacts with the pool
+    pub first_interaction: Option<Timestamp>,
+    pub last_interaction: Option<Timestamp>,
+}
+
+#[cw_serde]
+pub struct PortfolioResponse {
+    pub positions: Vec<Position>,
+    /// Value of all positions, one coin per denom
+    pub total_value: Vec<Coin>,
+    /// Rewards claimable right away, one coin per denom
+    pub pending_rewards: Vec<Coin>,
+    pub locks: Vec<PortfolioLock>,
+    /// Exits started but not paid out yet
+    pub pending_exits: Vec<PendingExit>,
+}
+
+#[cw_serde]
+pub struct Position {
+    pub denom: String,
+    pub amount: Uint128,
+    /// Time of the position's last balance change
+    pub since: Option<Timestamp>,
+}
+
+#[cw_serde]
+pub enum PortfolioLock {
+    /// Part of the stake still within the deposit lock
+    Deposit {
+        denom: String,
+        amount: Uint128,
+        unlocks_at_height: u64,
+    },
+    /// An imported stake that can be clawed back after `deadline` unless the owner interacts first
+    UnconfirmedImport {
+        denom: String,
+        amount: Uint128,
+        deadline: Expiration,
+    },
+}
+
+#[cw_serde]
+pub enum PendingExit {
+    /// A committed unstake; the amount stays hidden until it is revealed
+    CommittedUnstake { revealable_at_height: u64 },
+    /// An unstake waiting for the staker's confirmation
+    UnconfirmedUnstake {
+        denom: String,
+        amount: Uint128,
+        confirmable_at_height: u64,
+    },
+}
+
+#[cw_serde]
+pub struct NonceResponse {
+    pub nonce: u64,
+    pub signing_key: Option<Binary>,
+}
+
+#[cw_serde]
+pub enum ViolationKind {
+    /// A contract address holding a stake while contract stakers are rejected
+    ContractStaker,
+}
+
+#[cw_serde]
+pub struct Violation {
+    pub address: String,
+    pub stake: Uint128,
+    pub kind: ViolationKind,
+}
+
+#[cw_serde]
+pub struct HashedStakeEntry {
+    pub staker_hash: HexBinary,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct HashedStakesResponse {
+    pub stakes: Vec<HashedStakeEntry>,
+    /// Last hash scanned, or `None` once the listing reached the end
+    pub last_scanned: Option<HexBinary>,
+    /// Keyed by staker hash too, so the listing itself never names a staker
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub malformed: Vec<MalformedRecord>,
+}
+
+#[cw_serde]
+pub struct ViolationsResponse {
+    pub violations: Vec<Violation>,
+    /// Last staker checked, or `None` once the scan reached the end
+    pub last_scanned: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub malformed: Vec<MalformedRecord>,
+}
@@ -0,0 +1,228 @@
+//! Replay-protected verification of messages signed off-chain, for permits and approvals that a
+//! relayer submits on the signer's behalf.
+//!
+//! An address opts in by registering a secp256k1 public key with `ExecuteMsg::SetSigningKey`.
+//! Each [`SignedPayload`] names the chain, this contract, the action it authorizes and the signer's
+//! next nonce, so a signature is valid exactly once, here, for that action only, and only until the
+//! signer uses or invalidates the nonce.
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{from_json, Addr, Binary, DepsMut, Env};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+
+use crate::error::ContractError;
+use crate::state::{NONCES, SIGNING_KEYS};
+
+/// A JSON-encoded [`SignedPayload`] and the signer's secp256k1 signature over its sha256 hash
+#[cw_serde]
+pub struct Signed {
+    pub payload: Binary,
+    pub signature: Binary,
+}
+
+#[cw_serde]
+pub struct SignedPayload<T> {
+    pub signer: String,
+    pub nonce: u64,
+    pub chain_id: String,
+    pub contract: String,
+    /// What `msg` authorizes, so a signature for one kind of message can't pass as another
+    pub action: String,
+    pub msg: T,
+}
+
+/// Checks `signed` against the signer's registered key and consumes its nonce, returning the
+/// signer and the signed message. The payload must have been signed for `action`.
+pub fn verify_and_consume<T: DeserializeOwned>(
+    deps: DepsMut,
+    env: &Env,
+    action: &str,
+    signed: &Signed,
+) -> Result<(Addr, T), ContractError> {
+    let payload: SignedPayload<T> = from_json(&signed.payload)?;
+    if payload.chain_id != env.block.chain_id
+        || payload.contract != env.contract.address.as_str()
+        || payload.action != action
+    {
+        return Err(ContractError::WrongSigningDomain {});
+    }
+    let signer = deps.api.addr_validate(&payload.signer)?;
+    let pubkey = SIGNING_KEYS
+        .may_load(deps.storage, &signer)?
+        .ok_or(ContractError::NoSigningKey {})?;
+    let hash = Sha256::digest(signed.payload.as_slice());
+    // malformed signatures are just invalid ones
+    let valid = deps.api.secp256k1_verify(&hash, &signed.signature, &pubkey).unwrap_or(false);
+    if !valid {
+        return Err(ContractError::InvalidSignature {});
+    }
+
+    let expected = NONCES.may_load(deps.storage, &signer)?.unwrap_or_default();
+    if payload.nonce != expected {
+        return Err(ContractError::InvalidNonce { expected });
+    }
+    NONCES.save(deps.storage, &signer, &(expected + 1))?;
+    Ok((signer, payload.msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, instantiate, query};
+    use crate::msg::{ExecuteMsg, InstantiateMsg, NonceResponse, QueryMsg};
+    use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{to_json_binary, OwnedDeps, Uint128};
+    use k256::ecdsa::signature::Signer;
+    use k256::ecdsa::{Signature, SigningKey};
+
+    const APPROVE: &str = "approve";
+
+    #[cw_serde]
+    struct Approval {
+        spender: String,
+        amount: Uint128,
+    }
+
+    struct Setup {
+        deps: OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        alice: Addr,
+        key: SigningKey,
+    }
+
+    fn setup() -> Setup {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg { count: 0, initial_stakes: None, asset_metadata: None };
+        let owner = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), owner, msg).unwrap();
+
+        let alice = deps.api.addr_make("alice");
+        let key = SigningKey::from_slice(&[7; 32]).unwrap();
+        let pubkey = Binary::from(key.verifying_key().to_encoded_point(true).as_bytes());
+        let msg = ExecuteMsg::SetSigningKey { pubkey: Some(pubkey) };
+        execute(deps.as_mut(), mock_env(), message_info(&alice, &[]), msg).unwrap();
+        Setup { deps, alice, key }
+    }
+
+    fn sign(key: &SigningKey, payload: &SignedPayload<Approval>) -> Signed {
+        let payload = to_json_binary(payload).unwrap();
+        let signature: Signature = key.sign(payload.as_slice());
+        Signed {
+            payload,
+            signature: Binary::from(signature.to_bytes().as_slice()),
+        }
+    }
+
+    fn payload(signer: &Addr, nonce: u64) -> SignedPayload<Approval> {
+        SignedPayload {
+            signer: signer.to_string(),
+            nonce,
+            chain_id: mock_env().block.chain_id,
+            contract: mock_env().contract.address.to_string(),
+            action: APPROVE.to_string(),
+            msg: Approval {
+                spender: "relayer".to_string(),
+                amount: Uint128::new(5),
+            },
+        }
+    }
+
+    fn nonce(setup: &Setup) -> u64 {
+        let msg = QueryMsg::Nonce { address: setup.alice.to_string() };
+        let value: NonceResponse = from_json(query(setup.deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        value.nonce
+    }
+
+    #[test]
+    fn signed_message_is_accepted_once() {
+        let mut setup = setup();
+        let signed = sign(&setup.key, &payload(&setup.alice, 0));
+
+        let (signer, msg) = verify_and_consume::<Approval>(setup.deps.as_mut(), &mock_env(), APPROVE, &signed).unwrap();
+        assert_eq!(signer, setup.alice);
+        assert_eq!(msg.amount, Uint128::new(5));
+        assert_eq!(nonce(&setup), 1);
+
+        let err = verify_and_consume::<Approval>(setup.deps.as_mut(), &mock_env(), APPROVE, &signed).unwrap_err();
+        assert_eq!(err, ContractError::InvalidNonce { expected: 1 });
+
+        let signed = sign(&setup.key, &payload(&setup.alice, 1));
+        verify_and_consume::<Approval>(setup.deps.as_mut(), &mock_env(), APPROVE, &signed).unwrap();
+        assert_eq!(nonce(&setup), 2);
+    }
+
+    #[test]
+    fn future_and_past_nonces_are_rejected() {
+        let mut setup = setup();
+        let signed = sign(&setup.key, &payload(&setup.alice, 1));
+        let err = verify_and_consume::<Approval>(setup.deps.as_mut(), &mock_env(), APPROVE, &signed).unwrap_err();
+        assert_eq!(err, ContractError::InvalidNonce { expected: 0 });
+        // a rejected message does not consume the nonce
+        assert_eq!(nonce(&setup), 0);
+    }
+
+    #[test]
+    fn invalidated_nonce_cancels_outstanding_signature() {
+        let mut setup = setup();
+        let signed = sign(&setup.key, &payload(&setup.alice, 0));
+        let alice_info = message_info(&setup.alice, &[]);
+        execute(setup.deps.as_mut(), mock_env(), alice_info, ExecuteMsg::InvalidateNonce {}).unwrap();
+
+        let err = verify_and_consume::<Approval>(setup.deps.as_mut(), &mock_env(), APPROVE, &signed).unwrap_err();
+        assert_eq!(err, ContractError::InvalidNonce { expected: 1 });
+    }
+
+    #[test]
+    fn signatures_do_not_replay_across_chains_contracts_or_actions() {
+        let mut setup = setup();
+        let mut other_chain = payload(&setup.alice, 0);
+        other_chain.chain_id = "other-chain".to_string();
+        let mut other_contract = payload(&setup.alice, 0);
+        other_contract.contract = setup.deps.api.addr_make("other_pool").to_string();
+        let mut other_action = payload(&setup.alice, 0);
+        other_action.action = "revoke".to_string();
+
+        for payload in [other_chain, other_contract, other_action] {
+            let signed = sign(&setup.key, &payload);
+            let err = verify_and_consume::<Approval>(setup.deps.as_mut(), &mock_env(), APPROVE, &signed).unwrap_err();
+            assert_eq!(err, ContractError::WrongSigningDomain {});
+        }
+        assert_eq!(nonce(&setup), 0);
+    }
+
+    #[test]
+    fn signature_must_match_the_signers_key() {
+        let mut setup = setup();
+
+        // signed by a key other than the one alice registered
+        let other = SigningKey::from_slice(&[9; 32]).unwrap();
+        let signed = sign(&other, &payload(&setup.alice, 0));
+        let err = verify_and_consume::<Approval>(setup.deps.as_mut(), &mock_env(), APPROVE, &signed).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSignature {});
+
+        // a payload altered after signing
+        let mut signed = sign(&setup.key, &payload(&setup.alice, 0));
+        let mut tampered = payload(&setup.alice, 0);
+        tampered.msg.amount = Uint128::new(5_000);
+        signed.payload = to_json_binary(&tampered).unwrap();
+        let err = verify_and_consume::<Approval>(setup.deps.as_mut(), &mock_env(), APPROVE, &signed).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSignature {});
+
+        // addresses without a registered key can't sign at all
+        let bob = setup.deps.api.addr_make("bob");
+        let signed = sign(&setup.key, &payload(&bob, 0));
+        let err = verify_and_consume::<Approval>(setup.deps.as_mut(), &mock_env(), APPROVE, &signed).unwrap_err();
+        assert_eq!(err, ContractError::NoSigningKey {});
+
+        // rotating the key retires signatures made with the old one
+        let rotated = SigningKey::from_slice(&[11; 32]).unwrap();
+        let pubkey = Binary::from(rotated.verifying_key().to_encoded_point(true).as_bytes());
+        let msg = ExecuteMsg::SetSigningKey { pubkey: Some(pubkey) };
+        execute(setup.deps.as_mut(), mock_env(), message_info(&setup.alice, &[]), msg).unwrap();
+        let signed = sign(&setup.key, &payload(&setup.alice, 0));
+        let err = verify_and_consume::<Approval>(setup.deps.as_mut(), &mock_env(), APPROVE, &signed).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSignature {});
+        let signed = sign(&rotated, &payload(&setup.alice, 0));
+        verify_and_consume::<Approval>(setup.deps.as_mut(), &mock_env(), APPROVE, &signed).unwrap();
+    }
+}
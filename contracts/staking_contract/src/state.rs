@@ -1,8 +1,11 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Binary, Decimal, Empty, HexBinary, Int256, Timestamp, Uint128};
 use cw_storage_plus::{Item, Map};
+use cw_utils::{Duration, Expiration};
+
+use crate::keys;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct State {
@@ -10,5 +13,405 @@ pub struct State {
     pub owner: Addr,
 }
 
-pub const STATE: Item<State> = Item::new("state");
-pub const STAKES: Map<&Addr, Uint128> = Map::new("stakes"); // Definiția corectă
+/// Owner-tunable parameters
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct Config {
+    /// Ascending stake thresholds and the fee discount they unlock for integrating contracts
+    pub fee_discount_tiers: Vec<FeeDiscountTier>,
+    /// Reject stakes credited to contract addresses, for pools meant for human participants only
+    pub reject_contract_stakers: bool,
+    /// Display metadata of the staked asset; the chain's denom metadata is used when unset
+    pub asset_metadata: Option<AssetMetadata>,
+    /// Outflow threshold that raises a `tvl_drop_alert`; disabled when unset
+    pub tvl_alert: Option<TvlAlert>,
+    /// Recovery contract that receives the pool's funds on an emergency evacuation
+    pub escrow: Option<Addr>,
+    /// Let stakers withdraw their principal while the pool is paused
+    pub recovery_mode: bool,
+    /// Fixed gas rebate per action, paid from the owner-funded rebate pool; disabled when unset
+    pub rebate_rates: Option<RebateRates>,
+    /// End of a fixed-term campaign: new stakes are rejected afterwards, exits stay open
+    pub deposits_close_at: Option<Expiration>,
+    /// Ascending withdrawal-share thresholds and the exit fee charged above each; no fee when empty
+    #[serde(default)]
+    pub exit_fee_tiers: Vec<ExitFeeTier>,
+    /// Unstakes above a threshold must be committed to before they are revealed; off when unset
+    pub commit_reveal: Option<CommitReveal>,
+    /// Blocks a deposit stays locked for, starting with the block it lands in; off when zero
+    #[serde(default)]
+    pub deposit_lock_blocks: u64,
+    /// How aggregators and frontends list the pool
+    pub pool_metadata: Option<PoolMetadata>,
+    /// Check the pool's own balance before each payout, failing with a typed error instead of
+    /// deep in the bank module; off by default as it costs a query per payout
+    #[serde(default)]
+    pub liquidity_check: bool,
+    /// Serve `HashedStakes`, which lists every balance under a pseudonymous salted hash of its
+    /// staker's address, and page `Violations` by that hash. Set once `Rebuild { kind: staker_hashes }`
+    /// has indexed every staker.
+    #[serde(default)]
+    pub hashed_enumeration: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PoolMetadata {
+    pub name: String,
+    pub description: Option<String>,
+    pub website: Option<String>,
+    pub logo_uri: Option<String>,
+}
+
+/// Deposits still locked at the time of the latest one. Each deposit restarts the lock for the
+/// whole amount.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RecentDeposit {
+    pub height: u64,
+    pub amount: Uint128,
+}
+
+/// Part of a stake added in one block, aged from that block in blocks or seconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct StakeTranche {
+    pub amount: Uint128,
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CommitReveal {
+    /// Largest amount that can still be unstaked directly
+    pub threshold: Uint128,
+    /// Blocks that must pass between the commit and the reveal
+    pub reveal_delay: u64,
+}
+
+/// Hash of a pending large unstake, see [`crate::helpers::unstake_commitment`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct UnstakeCommit {
+    pub hash: HexBinary,
+    pub height: u64,
+}
+
+/// Blocks a contract staker's unstakes wait before it confirms them. A lower delay only applies
+/// once the current one has run out, so a compromised staker can't lift it on the spot.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct UnstakeConfirmation {
+    pub blocks: u64,
+    pub lowered: Option<LoweredDelay>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LoweredDelay {
+    pub blocks: u64,
+    pub from_height: u64,
+}
+
+impl UnstakeConfirmation {
+    /// Delay in force at `height`
+    pub fn blocks_at(&self, height: u64) -> u64 {
+        match &self.lowered {
+            Some(lowered) if height >= lowered.from_height => lowered.blocks,
+            _ => self.blocks,
+        }
+    }
+}
+
+/// An unstake waiting for its staker's confirmation; the stake stays in the pool until then
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingUnstake {
+    pub amount: Uint128,
+    pub min_receive: Option<Uint128>,
+    pub confirmable_at: u64,
+    /// How the payout is split, see `ExecuteMsg::Unstake`; empty pays the staker
+    #[serde(default)]
+    pub recipients: Vec<(Addr, Decimal)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RebateRates {
+    pub stake: Uint128,
+    pub unstake: Uint128,
+}
+
+/// Actions counted while a rebate program is active, and rebates not yet claimed
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct RebateAccount {
+    pub stakes: u64,
+    pub unstakes: u64,
+    pub accrued: Uint128,
+}
+
+/// Lifetime interaction counters of an address, kept regardless of its current stake
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct UserStats {
+    pub stakes: u64,
+    pub unstakes: u64,
+    pub claims: u64,
+    pub first_interaction: Timestamp,
+    pub last_interaction: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TvlAlert {
+    /// Length of the rolling window, in seconds or blocks
+    pub window: Duration,
+    /// Share of the TVL at the start of the window that may leave before alerting
+    pub max_drop: Decimal,
+    /// Pause the pool as soon as the alert fires
+    pub auto_pause: bool,
+}
+
+/// Outflows observed until the window `ends`, measured against the TVL the window opened with
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TvlWindow {
+    pub ends: Expiration,
+    pub start_tvl: Uint128,
+    pub outflow: Uint128,
+    pub alerted: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AssetMetadata {
+    pub symbol: String,
+    pub decimals: u32,
+    pub logo_uri: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct FeeDiscountTier {
+    pub min_stake: Uint128,
+    pub discount: Decimal,
+}
+
+/// Fee rate for withdrawals of at least `min_share` of the total staked, in a single transaction
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ExitFeeTier {
+    pub min_share: Decimal,
+    pub fee: Decimal,
+}
+
+/// Aggregate recomputed from the stakes map by `ExecuteMsg::Rebuild`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RebuildKind {
+    TotalStaked,
+    Distribution,
+    /// Index of stakers by salted hash behind `HashedStakes`, under a freshly drawn salt
+    StakerHashes,
+}
+
+impl RebuildKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RebuildKind::TotalStaked => "total_staked",
+            RebuildKind::Distribution => "distribution",
+            RebuildKind::StakerHashes => "staker_hashes",
+        }
+    }
+}
+
+/// Partial result of a rebuild spanning several transactions
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RebuildProgress {
+    pub kind: RebuildKind,
+    pub last_scanned: Option<Addr>,
+    pub stakers: u64,
+    pub total: Uint128,
+    /// Staker count per distribution bucket, indexed by the bucket's exponent
+    pub buckets: Vec<u64>,
+}
+
+/// Backup admin that may take over ownership once the owner has been inactive for `inactivity`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Guardian {
+    pub address: Addr,
+    pub inactivity: Duration,
+}
+
+/// Block of the owner's latest admin action
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Heartbeat {
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+/// Record of an emergency evacuation; once present the pool stays frozen
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Evacuation {
+    pub escrow: Addr,
+    pub amount: Uint128,
+    pub time: Timestamp,
+}
+
+/// Entries waiting in `UNSTAKE_ORDERS` and `PENDING_UNSTAKES`, counted as they come and go
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct QueueDepth {
+    pub unstake_orders: u64,
+    pub pending_unstakes: u64,
+}
+
+/// Cumulative flows since instantiation, which can't be reconstructed from current balances
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct LifetimeTotals {
+    pub staked: Uint128,
+    pub unstaked: Uint128,
+    /// Exit fees charged on the way out, part of `unstaked`
+    #[serde(default)]
+    pub fees: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerAccount {
+    /// Tokens held by the pool
+    Cash,
+    /// Stakes owed back to stakers
+    Principal,
+    /// Funded gas rebates not yet claimed
+    RewardsPayable,
+    /// Exit fees owed to the owner
+    FeesPayable,
+    /// The pool's own funds, which faucet payouts and evacuations draw on
+    Treasury,
+}
+
+impl LedgerAccount {
+    pub const ALL: [LedgerAccount; 5] = [
+        LedgerAccount::Cash,
+        LedgerAccount::Principal,
+        LedgerAccount::RewardsPayable,
+        LedgerAccount::FeesPayable,
+        LedgerAccount::Treasury,
+    ];
+}
+
+/// Balances of the double-entry ledger, debits positive and credits negative. Every posting
+/// debits and credits the same amount, so the balances sum to zero.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct Ledger {
+    pub cash: Int256,
+    pub principal: Int256,
+    pub rewards_payable: Int256,
+    pub fees_payable: Int256,
+    pub treasury: Int256,
+}
+
+impl Ledger {
+    pub fn balance_mut(&mut self, account: LedgerAccount) -> &mut Int256 {
+        match account {
+            LedgerAccount::Cash => &mut self.cash,
+            LedgerAccount::Principal => &mut self.principal,
+            LedgerAccount::RewardsPayable => &mut self.rewards_payable,
+            LedgerAccount::FeesPayable => &mut self.fees_payable,
+            LedgerAccount::Treasury => &mut self.treasury,
+        }
+    }
+
+    pub fn balance(&self, account: LedgerAccount) -> Int256 {
+        match account {
+            LedgerAccount::Cash => self.cash,
+            LedgerAccount::Principal => self.principal,
+            LedgerAccount::RewardsPayable => self.rewards_payable,
+            LedgerAccount::FeesPayable => self.fees_payable,
+            LedgerAccount::Treasury => self.treasury,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct DailyStats {
+    pub staked_in: Uint128,
+    pub unstaked_out: Uint128,
+    pub active_addresses: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct Profile {
+    pub display_name: Option<String>,
+    pub url: Option<String>,
+    pub avatar_hash: Option<String>,
+}
+
+/// An imported stake that governance may sweep if its owner never confirms it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Clawback {
+    pub amount: Uint128,
+    pub deadline: Expiration,
+}
+
+/// A pre-committed exit that anyone may trigger once `execute_at` has passed and its condition,
+/// if any, holds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnstakeOrder {
+    pub staker: Addr,
+    pub amount: Uint128,
+    pub execute_at: Expiration,
+    /// Part of `amount` paid to whoever triggers the order
+    pub bounty: Uint128,
+    /// Pool state that must hold at trigger time, on top of `execute_at` having passed
+    pub condition: Option<OrderCondition>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderCondition {
+    /// The staker's share of the pool is above `share`
+    ShareAbove { share: Decimal },
+    /// The pool's total stake is below `amount`
+    TotalStakedBelow { amount: Uint128 },
+}
+
+pub const STATE: Item<State> = Item::new(keys::STATE);
+pub const CONFIG: Item<Config> = Item::new(keys::CONFIG);
+pub const PAUSED: Item<bool> = Item::new(keys::PAUSED);
+pub const TVL_WINDOW: Item<TvlWindow> = Item::new(keys::TVL_WINDOW);
+pub const EVACUATION: Item<Evacuation> = Item::new(keys::EVACUATION);
+pub const STAKES: Map<&Addr, Uint128> = Map::new(keys::STAKES); // Definiția corectă
+/// Each staker's stake split by when it was added, oldest first
+pub const STAKE_TRANCHES: Map<&Addr, Vec<StakeTranche>> = Map::new(keys::STAKE_TRANCHES);
+pub const TOTAL_STAKED: Item<Uint128> = Item::new(keys::TOTAL_STAKED);
+pub const LIFETIME_TOTALS: Item<LifetimeTotals> = Item::new(keys::LIFETIME_TOTALS);
+/// Number of stakers per power-of-ten balance bucket, keyed by the exponent
+pub const DISTRIBUTION: Map<u8, u64> = Map::new(keys::DISTRIBUTION);
+/// Aggregated activity keyed by day number (unix time / 86400)
+pub const DAILY_STATS: Map<u64, DailyStats> = Map::new(keys::DAILY_STATS);
+/// Addresses already counted as active on a given day
+pub const DAILY_ACTIVE: Map<(u64, &Addr), Empty> = Map::new(keys::DAILY_ACTIVE);
+pub const PROFILES: Map<&Addr, Profile> = Map::new(keys::PROFILES);
+pub const CLAWBACKS: Map<&Addr, Clawback> = Map::new(keys::CLAWBACKS);
+pub const UNSTAKE_ORDERS: Map<u64, UnstakeOrder> = Map::new(keys::UNSTAKE_ORDERS);
+pub const NEXT_ORDER_ID: Item<u64> = Item::new(keys::NEXT_ORDER_ID);
+/// Rebate funds not yet credited to any staker
+pub const REBATE_POOL: Item<Uint128> = Item::new(keys::REBATE_POOL);
+pub const REBATE_ACCOUNTS: Map<&Addr, RebateAccount> = Map::new(keys::REBATE_ACCOUNTS);
+/// When each address may use the faucet again; only written by `testnet` builds
+pub const FAUCET_CLAIMS: Map<&Addr, Expiration> = Map::new(keys::FAUCET_CLAIMS);
+pub const USER_STATS: Map<&Addr, UserStats> = Map::new(keys::USER_STATS);
+/// At most one pending commit per staker; a new commit replaces it
+pub const UNSTAKE_COMMITS: Map<&Addr, UnstakeCommit> = Map::new(keys::UNSTAKE_COMMITS);
+/// Only written while a deposit lock is configured
+pub const RECENT_DEPOSITS: Map<&Addr, RecentDeposit> = Map::new(keys::RECENT_DEPOSITS);
+/// secp256k1 public keys addresses registered for off-chain signing, see [`crate::signing`]
+pub const SIGNING_KEYS: Map<&Addr, Binary> = Map::new(keys::SIGNING_KEYS);
+/// Next nonce each address must sign with; only ever increases
+pub const NONCES: Map<&Addr, u64> = Map::new(keys::NONCES);
+/// Only present while a rebuild is underway, which keeps stakes frozen until it completes
+pub const REBUILD: Item<RebuildProgress> = Item::new(keys::REBUILD);
+pub const GUARDIAN: Item<Guardian> = Item::new(keys::GUARDIAN);
+/// Refreshed by every owner-only message
+pub const OWNER_HEARTBEAT: Item<Heartbeat> = Item::new(keys::OWNER_HEARTBEAT);
+/// Contract stakers that opted into confirming their own unstakes
+pub const UNSTAKE_CONFIRMATIONS: Map<&Addr, UnstakeConfirmation> = Map::new(keys::UNSTAKE_CONFIRMATIONS);
+/// At most one per staker
+pub const PENDING_UNSTAKES: Map<&Addr, PendingUnstake> = Map::new(keys::PENDING_UNSTAKES);
+/// Sibling pools `MigrateStake` moves stake into right away, with the height each was added at
+pub const TRUSTED_POOLS: Map<&Addr, u64> = Map::new(keys::TRUSTED_POOLS);
+pub const LEDGER: Item<Ledger> = Item::new(keys::LEDGER);
+/// Salt of the staker hashes, present while the index is kept. Drawn when a rebuild starts; no
+/// query returns it, but it is readable raw state like the rest.
+pub const STAKER_HASH_SALT: Item<Binary> = Item::new(keys::STAKER_HASH_SALT);
+/// Absent until something is first queued
+pub const QUEUE_DEPTH: Item<QueueDepth> = Item::new(keys::QUEUE_DEPTH);
+/// Staker by salted hash, which makes the hashes pseudonymous rather than private. Entries hashed
+/// under an earlier salt are left behind and skipped.
+pub const STAKER_HASHES: Map<&[u8], Addr> = Map::new(keys::STAKER_HASHES);